@@ -1,8 +1,12 @@
 use itertools::Itertools;
 use seed::{prelude::*, *};
-use shared::{Event, EventData, Farm, Field, Req, Res, SyncData, Veggie, Silo};
+use shared::{
+    Achievement, Building, Catalog, Channel, Countermeasure, Event, EventData, EventIndex, Farm,
+    Field, Leaderboard, RejectReason, Req, Res, Role, Season, Side, Silo, SyncData, Trip,
+    UpdateError, Window, MAX_COOP_NAME, MAX_MEMBERS, MAX_MESSAGE_LENGTH, SPEED,
+};
 use std::{collections::HashMap, path::PathBuf, rc::Rc, iter::once};
-use strum::Display;
+use strum::{Display, IntoEnumIterator};
 
 #[cfg(not(debug_assertions))]
 const WS_URL: &str = "ws://boesiger.internet-box.ch/game/ws";
@@ -17,6 +21,13 @@ pub struct Model {
     web_socket: WebSocket,
     web_socket_reconnector: Option<StreamHandle>,
     state: Option<SyncData>,
+    unlocked: Vec<Achievement>,
+    rejected: Option<RejectReason>,
+    /// Waiting for a sync after an event couldn't be applied.
+    resyncing: bool,
+    leaderboards: Vec<Leaderboard>,
+    coop_name: String,
+    chat_text: String,
 }
 
 // ------ ------
@@ -30,6 +41,12 @@ fn init(_url: Url, orders: &mut impl Orders<Msg>) -> Model {
         web_socket: create_websocket(orders),
         web_socket_reconnector: None,
         state: None,
+        unlocked: Vec::new(),
+        rejected: None,
+        resyncing: false,
+        leaderboards: Vec::new(),
+        coop_name: String::new(),
+        chat_text: String::new(),
     }
 }
 
@@ -46,7 +63,18 @@ pub enum Msg {
     ReconnectWebSocket(usize),
     SendGameEvent(Event),
     ReceiveGameEvent(EventData),
+    SkipGameEvent(EventIndex),
     InitGameState(SyncData),
+    ServerRecovered(UpdateError),
+    AchievementUnlocked(Achievement),
+    DismissAchievements,
+    Rejected(RejectReason),
+    DismissRejection,
+    ReceiveLeaderboards(Vec<Leaderboard>),
+    CoopNameChanged(String),
+    CreateCoop,
+    ChatTextChanged(String),
+    SendChat(Channel),
 }
 
 fn update(msg: Msg, mut model: &mut Model, orders: &mut impl Orders<Msg>) {
@@ -55,6 +83,15 @@ fn update(msg: Msg, mut model: &mut Model, orders: &mut impl Orders<Msg>) {
         let serialized = rmp_serde::to_vec(&Req::Event(event)).unwrap();
         web_socket.send_bytes(&serialized).unwrap();
     };
+    // Asks for a fresh state once, the events until it arrives don't matter.
+    let resync = |resyncing: &mut bool, error: UpdateError| {
+        log!("Failed to apply event:", error.to_string());
+        if !*resyncing {
+            *resyncing = true;
+            let serialized = rmp_serde::to_vec(&Req::Resync).unwrap();
+            web_socket.send_bytes(&serialized).unwrap();
+        }
+    };
 
     match msg {
         Msg::WebSocketOpened => {
@@ -97,14 +134,54 @@ fn update(msg: Msg, mut model: &mut Model, orders: &mut impl Orders<Msg>) {
         }
         Msg::SendGameEvent(event) => send(event),
         Msg::ReceiveGameEvent(event) => {
+            if let Some(SyncData { state, catalog, .. }) = &mut model.state {
+                if let Err(error) = state.update(catalog, event) {
+                    resync(&mut model.resyncing, error);
+                }
+                // The server tells us about our own unlocks.
+                state.take_unlocked();
+            }
+        }
+        Msg::SkipGameEvent(event_idx) => {
             if let Some(SyncData { state, .. }) = &mut model.state {
-                if state.update(event).is_none() {
-                    web_socket.close(Some(4000), Some("invalid state")).unwrap();
+                if let Err(error) = state.skip(event_idx) {
+                    resync(&mut model.resyncing, error);
                 }
             }
         }
         Msg::InitGameState(sync_data) => {
             model.state = Some(sync_data);
+            model.resyncing = false;
+        }
+        Msg::ServerRecovered(error) => {
+            log!("Server went back to its last snapshot:", error.to_string());
+        }
+        Msg::AchievementUnlocked(achievement) => {
+            model.unlocked.push(achievement);
+        }
+        Msg::DismissAchievements => {
+            model.unlocked.clear();
+        }
+        Msg::Rejected(reason) => {
+            model.rejected = Some(reason);
+        }
+        Msg::DismissRejection => {
+            model.rejected = None;
+        }
+        Msg::ReceiveLeaderboards(leaderboards) => {
+            model.leaderboards = leaderboards;
+        }
+        Msg::CoopNameChanged(name) => {
+            model.coop_name = name;
+        }
+        Msg::CreateCoop => {
+            send(Event::CreateCoop(std::mem::take(&mut model.coop_name)));
+        }
+        Msg::ChatTextChanged(text) => {
+            model.chat_text = text;
+        }
+        Msg::SendChat(channel) => {
+            send(Event::SendMessage(channel, std::mem::take(&mut model.chat_text)));
         }
     }
 }
@@ -136,8 +213,23 @@ fn decode_message(message: WebSocketMessage, msg_sender: Rc<dyn Fn(Option<Msg>)>
                 Res::Event(event) => {
                     msg_sender(Some(Msg::ReceiveGameEvent(event)));
                 }
+                Res::Skip(event_idx) => {
+                    msg_sender(Some(Msg::SkipGameEvent(event_idx)));
+                }
                 Res::Sync(sync) => {
-                    msg_sender(Some(Msg::InitGameState(sync)));
+                    msg_sender(Some(Msg::InitGameState(*sync)));
+                }
+                Res::Achievement(achievement) => {
+                    msg_sender(Some(Msg::AchievementUnlocked(achievement)));
+                }
+                Res::Leaderboards(leaderboards) => {
+                    msg_sender(Some(Msg::ReceiveLeaderboards(leaderboards)));
+                }
+                Res::Rejected(reason) => {
+                    msg_sender(Some(Msg::Rejected(reason)));
+                }
+                Res::Recovered(error) => {
+                    msg_sender(Some(Msg::ServerRecovered(error)));
                 }
             }
         });
@@ -149,16 +241,320 @@ fn decode_message(message: WebSocketMessage, msg_sender: Rc<dyn Fn(Option<Msg>)>
 // ------ ------
 
 fn view(model: &Model) -> Node<Msg> {
-    if let Some(data) = &model.state {
-
-        let player = data.state.players.get(&data.user_id).unwrap();
+    if let Some((data, player)) = model
+        .state
+        .as_ref()
+        .and_then(|data| Some((data, data.state.me()?)))
+    {
         div![
             p![format!("user id, {}", data.user_id)],
+            p![format!("money, {}", player.farm.money)],
+            if model.unlocked.is_empty() {
+                empty![]
+            } else {
+                div![
+                    C!["unlocked"],
+                    model.unlocked.iter().map(|achievement| p![format!(
+                        "unlocked, {}, {}",
+                        achievement,
+                        achievement.description()
+                    )]),
+                    button!["Dismiss", ev(Ev::Click, |_| Msg::DismissAchievements)],
+                ]
+            },
+            if let Some(reason) = model.rejected {
+                div![
+                    C!["rejected"],
+                    p![reason.description()],
+                    button!["Dismiss", ev(Ev::Click, |_| Msg::DismissRejection)],
+                ]
+            } else {
+                empty![]
+            },
+            p![format!(
+                "achievements, {} / {}",
+                player.achievements.len(),
+                Achievement::iter().count()
+            )],
+            p![format!(
+                "{}, {}, then {}",
+                Season::at(data.state.time()),
+                data.state.climate().weather(data.state.time()),
+                data.state.climate().forecast(data.state.time()).iter().join(", ")
+            )],
+            p![format!(
+                "tractors ready, {} / {}",
+                player.farm.tractors.iter().filter(|t| t.is_ready()).count(),
+                player.farm.tractors.len()
+            )],
             div![
-                C!["grid"],
-                player.farm.render().into_iter().map(|draw| div![attrs!(
-                    At::Style => draw.style()
+                C!["shop"],
+                Building::iter().map(|building| button![
+                    format!("Buy {} ({})", building, player.farm.price(building)),
+                    ev(Ev::Click, move |_| Msg::SendGameEvent(Event::Buy(building))),
+                ]),
+                Building::iter()
+                    .flat_map(|building| (0..player.farm.count(building)).map(move |i| (building, i)))
+                    .filter_map(|(building, i)| player.farm
+                        .upgrade_price(building, i)
+                        .map(|price| button![
+                            format!("Upgrade {} {} ({})", building, i + 1, price),
+                            ev(Ev::Click, move |_| Msg::SendGameEvent(Event::Upgrade(building, i))),
+                        ])),
+                Countermeasure::iter().map(|countermeasure| button![
+                    if player.farm.is_protected(countermeasure, data.state.time()) {
+                        format!("Buy {} ({}, active)", countermeasure, countermeasure.price())
+                    } else {
+                        format!("Buy {} ({})", countermeasure, countermeasure.price())
+                    },
+                    ev(Ev::Click, move |_| Msg::SendGameEvent(Event::BuyCountermeasure(countermeasure))),
+                ]),
+            ],
+            if let Some(coop) = data.state.coop() {
+                let leader = coop.members.get(&data.user_id) == Some(&Role::Leader);
+                div![
+                    C!["coop"],
+                    h3![format!("co-op {}", coop.name)],
+                    ul![coop.seniority.iter().map(|member| {
+                        let member = *member;
+                        li![
+                            format!(
+                                "{}, {}",
+                                data.state.username(member).unwrap_or("unknown"),
+                                coop.members.get(&member).map_or(Role::Member, |role| *role)
+                            ),
+                            IF!(leader && member != data.user_id => button![
+                                "Kick",
+                                ev(Ev::Click, move |_| Msg::SendGameEvent(Event::KickMember(member))),
+                            ]),
+                        ]
+                    })],
+                    p![format!(
+                        "storage, {} / {}, {}",
+                        coop.silo.storage.len(),
+                        coop.silo.max_storage,
+                        coop.silo.storage.iter()
+                            .map(|v| format!("{} {}", v.qty(), data.catalog.name(v.veggie())))
+                            .join(", ")
+                    )],
+                    (0..player.farm.silos.len()).map(|i| button![
+                        format!("Deposit from silo {}", i + 1),
+                        ev(Ev::Click, move |_| Msg::SendGameEvent(Event::Deposit(i))),
+                    ]),
+                    button!["Plant from co-op", ev(Ev::Click, |_| Msg::SendGameEvent(Event::PlantFromCoop))],
+                    button!["Leave", ev(Ev::Click, |_| Msg::SendGameEvent(Event::LeaveCoop))],
+                ]
+            } else {
+                div![
+                    C!["coop"],
+                    ul![data.state.coops().iter().map(|(coop_id, coop)| {
+                        let coop_id = *coop_id;
+                        li![
+                            format!("co-op {}, {} / {} members", coop.name, coop.members.len(), MAX_MEMBERS),
+                            button!["Join", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::JoinCoop(coop_id)))],
+                        ]
+                    })],
+                    input![
+                        attrs!(
+                            At::Type => "text",
+                            At::Value => model.coop_name,
+                            At::MaxLength => MAX_COOP_NAME,
+                            At::Placeholder => "co-op name",
+                        ),
+                        input_ev(Ev::Input, Msg::CoopNameChanged),
+                    ],
+                    button!["Found co-op", ev(Ev::Click, |_| Msg::CreateCoop)],
+                ]
+            },
+            ul![
+                C!["trucks"],
+                player.farm.trucks.iter().enumerate().map(|(i, truck)| li![format!(
+                    "truck {}, {}, {}",
+                    i + 1,
+                    truck
+                        .veggies()
+                        .map(|v| format!("{} {}", v.qty(), data.catalog.name(v.veggie())))
+                        .unwrap_or_else(|| "empty".to_string()),
+                    match truck.trip {
+                        None => "at home".to_string(),
+                        Some(Trip::Outbound { user_id, .. }) => {
+                            format!("driving to {} ({}s)", user_id, truck.wait / SPEED)
+                        }
+                        Some(Trip::Delivery { contract }) => {
+                            format!("delivering contract {} ({}s)", contract, truck.wait / SPEED)
+                        }
+                        Some(Trip::Returning) => format!("returning ({}s)", truck.wait / SPEED),
+                    }
                 )])
+            ],
+            div![
+                C!["dock"],
+                (0..player.farm.silos.len()).map(|i| button![
+                    format!("Load truck from silo {}", i + 1),
+                    ev(Ev::Click, move |_| Msg::SendGameEvent(Event::LoadTruck(i))),
+                ]),
+            ],
+            ul![
+                C!["farms"],
+                data.state.others().map(|(user_id, other)| li![
+                    C![IF!(other.online => "online")],
+                    format!(
+                        "{}, {} of {} trucks on the road",
+                        other.username,
+                        other.trucks.iter().filter(|truck| truck.trip.is_some()).count(),
+                        other.trucks.len()
+                    ),
+                    player.farm.trucks.iter().enumerate()
+                        .filter(|(_, truck)| truck.is_ready() && truck.veggies().is_some())
+                        .flat_map(|(offered, _)| (0..other.trucks.len()).map(move |requested| button![
+                            format!("Offer truck {} for their truck {}", offered + 1, requested + 1),
+                            ev(Ev::Click, move |_| Msg::SendGameEvent(Event::ProposeTrade(offered, user_id, requested))),
+                        ]))
+                        .collect::<Vec<_>>(),
+                ])
+            ],
+            ul![
+                C!["contracts"],
+                data.state.contracts().iter().map(|(contract_id, contract)| {
+                    let contract_id = *contract_id;
+                    li![
+                        format!(
+                            "contract {}, {} {} for {} bonus, {}s left",
+                            contract_id,
+                            contract.qty,
+                            data.catalog.name(contract.veggie),
+                            contract.reward,
+                            contract.deadline.saturating_sub(data.state.time()) / SPEED
+                        ),
+                        if contract.taken_by.is_none() {
+                            player.farm.trucks.iter().enumerate()
+                                .filter(|(_, truck)| truck.is_ready() && truck.veggies()
+                                    .is_some_and(|v| v.veggie() == contract.veggie && v.qty() >= contract.qty))
+                                .map(|(i, _)| button![
+                                    format!("Send truck {}", i + 1),
+                                    ev(Ev::Click, move |_| Msg::SendGameEvent(Event::DeliverContract(i, contract_id))),
+                                ])
+                                .collect::<Vec<_>>()
+                        } else {
+                            Vec::new()
+                        }
+                    ]
+                })
+            ],
+            ul![
+                C!["offers"],
+                data.state.offers().iter()
+                    .filter(|(_, offer)| offer.to == data.user_id || offer.from == data.user_id)
+                    .map(|(offer_id, offer)| {
+                        let offer_id = *offer_id;
+                        if offer.to == data.user_id {
+                            li![
+                                format!(
+                                    "offer from {} for truck {}",
+                                    data.state.username(offer.from).unwrap_or("unknown"),
+                                    offer.requested_truck + 1
+                                ),
+                                button!["Accept", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::AcceptTrade(offer_id)))],
+                                button!["Decline", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::DeclineTrade(offer_id)))],
+                            ]
+                        } else {
+                            li![
+                                format!(
+                                    "offer to {} with truck {}",
+                                    data.state.username(offer.to).unwrap_or("unknown"),
+                                    offer.offered_truck + 1
+                                ),
+                                button!["Withdraw", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::DeclineTrade(offer_id)))],
+                            ]
+                        }
+                    })
+            ],
+            div![
+                C!["market"],
+                data.catalog.iter().map(|veggie| {
+                    let price = data.state.prices().price(&data.catalog, veggie);
+                    let history = data.state.prices().veggies
+                        .get(&veggie)
+                        .map(|v| v.history.iter().rev().take(10).join(", "))
+                        .unwrap_or_default();
+                    div![
+                        format!("{}, {} each (before, {})", data.catalog.name(veggie), price, history),
+                        button!["Buy 1", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::PlaceOrder(Side::Buy, veggie, 1, price)))],
+                        button!["Sell 1", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::PlaceOrder(Side::Sell, veggie, 1, price)))],
+                    ]
+                }),
+                ul![data.state.market().orders_of(data.user_id).map(|(order_id, order)| {
+                    let order_id = *order_id;
+                    li![
+                        format!(
+                            "{:?} {} {} at {}",
+                            order.side,
+                            order.qty,
+                            data.catalog.name(order.veggie),
+                            order.price
+                        ),
+                        button!["Cancel", ev(Ev::Click, move |_| Msg::SendGameEvent(Event::CancelOrder(order_id)))],
+                    ]
+                })],
+                ul![data.state.market().fills.iter()
+                    .filter(|fill| fill.buyer == data.user_id || fill.seller == data.user_id)
+                    .map(|fill| li![format!(
+                        "{} {} {} at {}",
+                        if fill.buyer == data.user_id { "bought" } else { "sold" },
+                        fill.qty,
+                        data.catalog.name(fill.veggie),
+                        fill.price
+                    )])],
+            ],
+            div![
+                C!["chat"],
+                ul![data.state.chat().iter().map(|message| {
+                    let name = |user_id| data.state.username(user_id).unwrap_or("unknown");
+                    li![match message.channel {
+                        Channel::Global => format!("{}: {}", name(message.from), message.text),
+                        Channel::Direct(to) => format!("{} to {}: {}", name(message.from), name(to), message.text),
+                    }]
+                })],
+                input![
+                    attrs!(
+                        At::Type => "text",
+                        At::Value => model.chat_text,
+                        At::MaxLength => MAX_MESSAGE_LENGTH,
+                        At::Placeholder => "message",
+                    ),
+                    input_ev(Ev::Input, Msg::ChatTextChanged),
+                ],
+                button!["Send", ev(Ev::Click, |_| Msg::SendChat(Channel::Global))],
+                data.state.others()
+                    .map(|(user_id, player)| {
+                        button![
+                            format!("Send to {}", player.username),
+                            ev(Ev::Click, move |_| Msg::SendChat(Channel::Direct(user_id))),
+                        ]
+                    }),
+            ],
+            div![
+                C!["leaderboards"],
+                model.leaderboards.iter()
+                    .filter(|leaderboard| leaderboard.window == Window::Day)
+                    .map(|leaderboard| div![
+                        h3![format!("{}, {}", leaderboard.metric, leaderboard.window)],
+                        ol![leaderboard.entries.iter().take(3).map(|entry| li![
+                            C![IF!(entry.user_id == data.user_id => "me")],
+                            format!("{}, {}", entry.username, entry.value)
+                        ])],
+                    ]),
+                a![attrs!(At::Href => "/leaderboards"), "All leaderboards"],
+            ],
+            div![
+                C!["grid"],
+                player.farm.render(&data.catalog).into_iter().map(|draw| div![
+                    C![draw.class],
+                    attrs!(
+                        At::Style => draw.style()
+                    ),
+                    draw.action.map(|event| ev(Ev::Click, move |_| Msg::SendGameEvent(event)))
+                ])
             ]
         ]
     } else {
@@ -176,13 +572,13 @@ pub fn start() {
 }
 
 trait Render {
-    fn render(&self) -> Vec<Draw>
+    fn render(&self, catalog: &Catalog) -> Vec<Draw>
     where
         Self: Sized;
 }
 
 impl Render for Farm {
-    fn render(&self) -> Vec<Draw>
+    fn render(&self, catalog: &Catalog) -> Vec<Draw>
     where
         Self: Sized,
     {
@@ -190,18 +586,22 @@ impl Render for Farm {
             .iter()
             .enumerate()
             .flat_map(|(i, f)| {
-                f.render()
+                f.render(catalog)
                     .into_iter()
-                    .map(move |d| d.mov((i as i32 * 3) % 9, 1 + (i as i32 * 3) / 9, 0))
+                    .map(move |d| d
+                        .mov((i as i32 * 3) % 9, 1 + (i as i32 * 3) / 9, 0)
+                        .on_click(Event::HarvestField(i)))
             });
 
         let silos = self.silos
             .iter()
             .enumerate()
             .flat_map(|(i, f)| {
-                f.render()
+                f.render(catalog)
                     .into_iter()
-                    .map(move |d| d.mov(i as i32 + 3, i as i32 + 5, 0))
+                    .map(move |d| d
+                        .mov(i as i32 + 3, i as i32 + 5, 0)
+                        .on_click(Event::PlantField(i)))
             });
         
         silos
@@ -211,21 +611,42 @@ impl Render for Farm {
 }
 
 impl Render for Field {
-    fn render(&self) -> Vec<Draw>
+    fn render(&self, catalog: &Catalog) -> Vec<Draw>
     where
         Self: Sized,
     {
-        vec![Draw {
+        let field = once(Draw {
             x: 0,
             y: 0,
             z: 0,
             texture: Texture::Field,
-        }]
+            action: None,
+            class: self.disease.map(|_| "diseased"),
+        });
+
+        let crops = self.veggies
+            .iter()
+            .filter_map(|veggies| Some((veggies.qty(), catalog.get(veggies.veggie())?)))
+            .flat_map(|(qty, spec)| {
+                let growth = self.growth();
+                (0..qty).map(move |i| Draw {
+                    x: i as i32,
+                    y: 1,
+                    z: 1,
+                    texture: Texture::Veggie(spec.sprite.clone()),
+                    action: None,
+                    class: growth.map(<&'static str>::from),
+                })
+            });
+
+        field
+            .chain(crops)
+            .collect()
     }
 }
 
 impl Render for Silo {
-    fn render(&self) -> Vec<Draw>
+    fn render(&self, catalog: &Catalog) -> Vec<Draw>
     where
         Self: Sized,
     {
@@ -234,18 +655,24 @@ impl Render for Silo {
             y: 0,
             z: 0,
             texture: Texture::SiloBackBottom,
+            action: None,
+            class: None,
         })
             .chain((1..(self.max_storage - 1)).map(|i| Draw {
                 x: 0,
                 y: -(i as i32),
                 z: 0,
-                texture: Texture::SiloBackMiddle
+                texture: Texture::SiloBackMiddle,
+                action: None,
+                class: None,
             }))
             .chain(once(Draw {
                 x: 0,
                 y: -(self.max_storage as i32 - 1),
                 z: 0,
-                texture: Texture::SiloBackTop
+                texture: Texture::SiloBackTop,
+                action: None,
+                class: None,
             }));
 
         let front = once(Draw {
@@ -253,29 +680,38 @@ impl Render for Silo {
             y: 0,
             z: 2,
             texture: Texture::SiloFrontBottom,
+            action: None,
+            class: None,
         })
             .chain((1..(self.max_storage - 1)).map(|i| Draw {
                 x: 0,
                 y: -(i as i32),
                 z: 2,
-                texture: Texture::SiloFrontMiddle
+                texture: Texture::SiloFrontMiddle,
+                action: None,
+                class: None,
             }))
             .chain(once(Draw {
                 x: 0,
                 y: -(self.max_storage as i32 - 1),
                 z: 2,
-                texture: Texture::SiloFrontTop
+                texture: Texture::SiloFrontTop,
+                action: None,
+                class: None,
             }));
 
         let veggies = self.storage
             .iter()
             .enumerate()
-            .map(|(i, veggie)| {
+            .filter_map(|(i, veggie)| Some((i, catalog.get(veggie.veggie())?)))
+            .map(|(i, spec)| {
                 Draw {
                     x: 0,
                     y: -(i as i32),
                     z: 1,
-                    texture: Texture::Veggie(veggie.veggie())
+                    texture: Texture::Veggie(spec.sprite.clone()),
+                    action: None,
+                    class: None,
                 }
             });
 
@@ -291,6 +727,8 @@ struct Draw {
     y: i32,
     z: i32,
     texture: Texture,
+    action: Option<Event>,
+    class: Option<&'static str>,
 }
 
 impl Draw {
@@ -319,6 +757,11 @@ impl Draw {
         self.z += dz;
         self
     }
+
+    fn on_click(mut self, event: Event) -> Self {
+        self.action = Some(event);
+        self
+    }
 }
 
 #[derive(Display)]
@@ -331,14 +774,13 @@ enum Texture {
     SiloFrontTop,
     SiloFrontMiddle,
     SiloFrontBottom,
-    Veggie(Veggie),
+    Veggie(String),
 }
 
 impl Texture {
     fn path(&self) -> PathBuf {
         match self {
-            Self::Veggie(veggie) => PathBuf::from(veggie.to_string().replace(" ", "-").to_lowercase())
-                .with_extension("png"),
+            Self::Veggie(sprite) => PathBuf::from(sprite),
             _ => PathBuf::from(self.to_string().replace(" ", "-").to_lowercase())
                 .with_extension("png"),
         }
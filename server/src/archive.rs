@@ -0,0 +1,138 @@
+use crate::{game::WORLD_VERSION, WorldError};
+use serde::de::IgnoredAny;
+use shared::{Player, State, Time, UserId, ONE_DAY, ONE_HOUR, SPEED};
+use sqlx::{Executor, Sqlite, SqlitePool, Transaction};
+
+/// Ticks between two looks for farms to archive.
+pub const ARCHIVE_INTERVAL: Time = ONE_HOUR * SPEED;
+
+/// When farms of players who stopped playing are moved out of the live
+/// state. Configured in days with `ARCHIVE_AFTER_DAYS`.
+#[derive(Clone, Copy, Debug)]
+pub struct ArchivePolicy {
+    /// Seconds without activity after which a farm is archived.
+    pub inactive_after: Time,
+}
+
+impl ArchivePolicy {
+    pub fn from_env() -> Self {
+        let days = std::env::var("ARCHIVE_AFTER_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(7);
+
+        ArchivePolicy {
+            inactive_after: days * ONE_DAY,
+        }
+    }
+
+    /// Inactive players whose farms have no trucks left on the road.
+    pub fn candidates(&self, state: &State) -> Vec<UserId> {
+        if !state.time.is_multiple_of(ARCHIVE_INTERVAL) {
+            return Vec::new();
+        }
+
+        state
+            .players
+            .iter()
+            .filter(|(_, player)| self.applies_to(player, state.time))
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
+    pub fn applies_to(&self, player: &Player, time: Time) -> bool {
+        !player.is_active(time, self.inactive_after) && player.is_idle(time)
+    }
+}
+
+/// Stores a farm with the version of the world it comes from, see
+/// [`WORLD_VERSION`].
+pub async fn store(pool: &SqlitePool, user_id: UserId, player: &Player) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+            INSERT OR REPLACE INTO archived_players (user_id, data)
+            VALUES ($1, $2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(rmp_serde::to_vec(&(WORLD_VERSION, player)).unwrap())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// An archived player, if there is one.
+pub async fn load<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    user_id: UserId,
+) -> Result<Option<Player>, WorldError> {
+    let result: Option<(Vec<u8>,)> = sqlx::query_as(
+        r#"
+            SELECT data
+            FROM archived_players
+            WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await?;
+
+    let Some((data,)) = result else {
+        return Ok(None);
+    };
+    let player = match rmp_serde::from_slice::<(u32, IgnoredAny)>(&data[..]) {
+        Ok((WORLD_VERSION, _)) => rmp_serde::from_slice::<(u32, Player)>(&data[..])?.1,
+        Ok((version, _)) => return Err(WorldError::UnknownVersion(version)),
+        // Farms archived before there were versions hold just the player.
+        Err(_) => rmp_serde::from_slice::<Player>(&data[..])?,
+    };
+
+    Ok(Some(player))
+}
+
+/// Takes an archived farm out, under the name its player goes by now. It is
+/// only gone once the transaction that stores the world it is back in is
+/// committed.
+pub async fn take(
+    pool: &SqlitePool,
+    user_id: UserId,
+) -> Result<Option<(Player, Transaction<'static, Sqlite>)>, WorldError> {
+    let mut transaction = pool.begin().await?;
+    let Some(mut player) = load(&mut transaction, user_id).await? else {
+        return Ok(None);
+    };
+    // Renames while archived went only to the users table.
+    let (username,): (String,) = sqlx::query_as(
+        r#"
+            SELECT username
+            FROM users
+            WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut transaction)
+    .await?;
+    player.username = username;
+
+    Ok(Some((player, transaction)))
+}
+
+/// Removes a player from the archive, only along with storing the world
+/// their farm is back in.
+pub async fn remove<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    user_id: UserId,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+            DELETE FROM archived_players
+            WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use shared::Catalog;
+use std::path::PathBuf;
+
+/// Loads the veggie catalog from the file named by `CATALOG`, or from
+/// `veggies.toml` next to the server manifest.
+pub fn load() -> Result<Catalog, Box<dyn std::error::Error>> {
+    let path = std::env::var("CATALOG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("veggies.toml"));
+
+    let catalog: Catalog = toml::from_str(&std::fs::read_to_string(path)?)?;
+    catalog.validate()?;
+
+    Ok(catalog)
+}
@@ -43,6 +43,17 @@ pub async fn setup() -> Result<SqlitePool, Box<dyn std::error::Error>> {
     .execute(&mut transaction)
     .await?;
 
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS archived_players (
+            user_id INTEGER PRIMARY KEY REFERENCES users(user_id),
+            data BLOB
+        )
+    "#,
+    )
+    .execute(&mut transaction)
+    .await?;
+
     transaction.commit().await?;
 
     Ok(pool)
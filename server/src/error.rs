@@ -12,6 +12,17 @@ pub enum ServerError {
     SqliteError(#[from] sqlx::Error),
 }
 
+/// A saved world or archived farm that can't be loaded.
+#[derive(Debug, Error)]
+pub enum WorldError {
+    #[error(transparent)]
+    SqliteError(#[from] sqlx::Error),
+    #[error(transparent)]
+    DecodeError(#[from] rmp_serde::decode::Error),
+    #[error("unknown world version {0}")]
+    UnknownVersion(u32),
+}
+
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
         match self {
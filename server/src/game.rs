@@ -8,9 +8,12 @@ use axum::{
 use axum_sessions::extractors::ReadableSession;
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use serde::{Deserialize, Serialize};
-use shared::{Event, EventData, SyncData, UserId, SPEED};
-use sqlx::SqlitePool;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use shared::{
+    Achievement, Catalog, Event, EventData, Filter, Leaderboard, RejectReason, SyncData,
+    UpdateError, UserId, SPEED,
+};
+use sqlx::{Sqlite, SqlitePool, Transaction};
 use std::{sync::Arc, time::Duration};
 use tokio::{
     sync::{broadcast, mpsc, RwLock},
@@ -23,20 +26,46 @@ pub struct PartialEventData {
     pub user_id: Option<UserId>,
 }
 
-use crate::ServerError;
+/// Everything the game loop hands out to the connections, each of them
+/// picks what concerns its player.
+#[derive(Debug, Clone)]
+pub enum Broadcast {
+    Event(EventData, Arc<Filter>),
+    Achievement(UserId, Achievement),
+    Rejected(UserId, RejectReason),
+    /// Asked for by a connection that fell out of step.
+    Resync(UserId),
+    Recovered(UpdateError),
+    Leaderboards(Arc<Vec<Leaderboard>>),
+}
+
+use crate::{
+    archive::{self, ArchivePolicy},
+    leaderboard::{History, BROADCAST_INTERVAL},
+    refill, ServerError, WorldError,
+};
+
+/// Saved along with the world and bumped whenever the state changes shape,
+/// older worlds are migrated when they are loaded.
+pub const WORLD_VERSION: u32 = 1;
 
 #[derive(Clone)]
 pub struct GameState(Arc<GameStateImpl>);
 
 struct GameStateImpl {
     state: RwLock<shared::State>,
-    res_sender: broadcast::Sender<EventData>,
+    catalog: Catalog,
+    history: RwLock<History>,
+    res_sender: broadcast::Sender<Broadcast>,
     req_sender: mpsc::UnboundedSender<PartialEventData>,
 }
 
 impl GameState {
-    async fn load_game(pool: &SqlitePool) -> Option<shared::State> {
-        let result: Result<Option<(Vec<u8>,)>, _> = sqlx::query_as(
+    async fn load_game(
+        pool: &SqlitePool,
+        catalog: &Catalog,
+    ) -> Result<Option<shared::State>, WorldError> {
+        let result: Option<(Vec<u8>,)> = sqlx::query_as(
             r#"
                 SELECT data
                 FROM worlds
@@ -44,36 +73,68 @@ impl GameState {
             "#,
         )
         .fetch_optional(pool)
-        .await;
-
-        result
-            .unwrap()
-            .map(|(data,)| rmp_serde::from_slice(&data[..]).unwrap())
+        .await?;
+
+        let Some((data,)) = result else {
+            return Ok(None);
+        };
+        let state = match rmp_serde::from_slice::<(u32, IgnoredAny)>(&data[..]) {
+            Ok((WORLD_VERSION, _)) => rmp_serde::from_slice::<(u32, shared::State)>(&data[..])?.1,
+            Ok((version, _)) => return Err(WorldError::UnknownVersion(version)),
+            // Worlds saved before there were versions hold just the state.
+            Err(_) => rmp_serde::from_slice::<shared::legacy::State>(&data[..])?.migrate(catalog),
+        };
+
+        Ok(Some(state))
     }
 
-    async fn store_game(pool: &SqlitePool, state: &shared::State) {
+    async fn store_game<'e>(
+        executor: impl sqlx::Executor<'e, Database = sqlx::Sqlite>,
+        state: &shared::State,
+    ) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
                 INSERT OR REPLACE INTO worlds (name, data)
                 VALUES ('world', $1)
             "#,
         )
-        .bind(rmp_serde::to_vec(state).unwrap())
-        .execute(pool)
-        .await
-        .unwrap();
+        .bind(rmp_serde::to_vec(&(WORLD_VERSION, state)).unwrap())
+        .execute(executor)
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn new(pool: SqlitePool) -> GameState {
+    /// Stores the world a farm is back in and takes it out of the archive,
+    /// or does neither.
+    async fn store_restored(
+        mut transaction: Transaction<'_, Sqlite>,
+        state: &shared::State,
+        user_id: UserId,
+    ) -> Result<(), sqlx::Error> {
+        GameState::store_game(&mut transaction, state).await?;
+        archive::remove(&mut transaction, user_id).await?;
+        transaction.commit().await
+    }
+
+    pub async fn new(pool: SqlitePool, catalog: Catalog) -> Result<GameState, WorldError> {
         let (req_sender, mut req_receiver) = mpsc::unbounded_channel::<PartialEventData>();
-        let (res_sender, _res_receiver) = broadcast::channel::<EventData>(64);
+        let (res_sender, _res_receiver) = broadcast::channel::<Broadcast>(64);
 
         let req_sender_clone = req_sender.clone();
 
-        let game = RwLock::new(GameState::load_game(&pool).await.unwrap_or_default());
+        let mut game = GameState::load_game(&pool, &catalog)
+            .await?
+            .unwrap_or_else(shared::State::new);
+        // Nobody is connected to a server that just started.
+        game.reset_connections();
+        game.refills = refill::from_env();
+        let game = RwLock::new(game);
         //let game = RwLock::new(State::default());
         let game_state = Arc::new(GameStateImpl {
             state: game,
+            catalog,
+            history: RwLock::new(History::default()),
             res_sender,
             req_sender,
         });
@@ -97,68 +158,238 @@ impl GameState {
         tokio::spawn(async move {
             let GameStateImpl {
                 state: game,
+                catalog,
+                history,
                 res_sender,
-                ..
+                req_sender,
             } = &*game_state_clone;
 
             let mut rng = SmallRng::from_entropy();
+            let policy = ArchivePolicy::from_env();
+            // Events of the server since the last snapshot, nobody would
+            // send them again if the world had to be recovered.
+            let mut unsaved: Vec<EventData> = Vec::new();
 
             while let Some(PartialEventData { event, user_id }) = req_receiver.recv().await {
                 let event = match event {
-                    // Valid only as server-sent events.
-                    Event::Tick
-                    | Event::AddPlayer(_, _)
-                    | Event::EditPlayer(_, _)
-                    | Event::RemovePlayer(_)
-                        if user_id.is_some() =>
+                    // Only ever made up for the players, never applied.
+                    Event::Redacted(_) => None,
+                    // Valid only as player-sent events.
+                    Event::ProposeTrade(_, _, _)
+                    | Event::AcceptTrade(_)
+                    | Event::DeclineTrade(_)
+                    | Event::DeliverContract(_, _)
+                    | Event::CreateCoop(_)
+                    | Event::JoinCoop(_)
+                    | Event::LeaveCoop
+                    | Event::KickMember(_)
+                    | Event::Deposit(_)
+                    | Event::PlantFromCoop
+                    | Event::SendMessage(_, _)
+                    | Event::PlantField(_)
+                    | Event::HarvestField(_)
+                    | Event::LoadTruck(_)
+                    | Event::Buy(_)
+                    | Event::Upgrade(_, _)
+                    | Event::BuyCountermeasure(_)
+                    | Event::PlaceOrder(_, _, _, _)
+                    | Event::CancelOrder(_)
+                        if user_id.is_none() =>
                     {
                         None
                     }
                     event => Some(event),
                 };
 
-                if let Some(event) = event {
+                if let Some(mut event) = event {
                     let state = game.read().await;
 
+                    // Players' events that can't go through aren't broadcast.
+                    if let Some(user_id) = user_id {
+                        if let Err(reason) = state.validate(catalog, &event, user_id) {
+                            res_sender.send(Broadcast::Rejected(user_id, reason)).ok();
+                            continue;
+                        }
+                    }
+
+                    // Farms leave the archive in the same transaction that
+                    // stores the world they are back in. The archive is read
+                    // again, a player may have asked to come back twice.
+                    let mut restored = None;
+                    if let Event::RestorePlayer(user_id, _) = &event {
+                        let user_id = *user_id;
+                        // Farms that are back but couldn't leave the archive
+                        // aren't restored over.
+                        if state.players.contains_key(&user_id) {
+                            continue;
+                        }
+                        match archive::take(&pool, user_id).await {
+                            Ok(Some((player, transaction))) => {
+                                event = Event::RestorePlayer(user_id, Box::new(player));
+                                restored = Some((user_id, transaction));
+                            }
+                            Ok(None) => continue,
+                            Err(error) => {
+                                tracing::error!("failed to restore player {}, {}", user_id, error);
+                                continue;
+                            }
+                        }
+                    }
+
+                    // Farms are removed only once they are safely archived.
+                    if let Event::RemovePlayer(removed) = event {
+                        match state.players.get(&removed) {
+                            Some(player) if policy.applies_to(player, state.time) => {
+                                if let Err(error) = archive::store(&pool, removed, player).await {
+                                    tracing::error!(
+                                        "failed to archive player {}, {}",
+                                        removed,
+                                        error
+                                    );
+                                    continue;
+                                }
+                            }
+                            // The player came back in the meantime.
+                            _ => continue,
+                        }
+                    }
+
+                    // Ticks are rolled from the secret seed, see offline.rs.
+                    let seed = match event {
+                        Event::Tick => state.next_tick_seed(),
+                        _ => rng.gen(),
+                    };
                     let event = EventData {
                         user_id,
                         event,
                         event_idx: state.next_event_idx,
-                        seed: rng.gen(),
+                        seed,
                     };
+                    let mut filter = event.filter();
 
                     drop(state);
 
-                    res_sender.send(event.clone()).ok();
-                    if game.write().await.update(event).is_none() {
-                        panic!("Invalid game state.");
+                    let mut state = game.write().await;
+                    if let Err(error) = state.update(catalog, event.clone()) {
+                        tracing::error!("failed to apply event {}, {}", event.event_idx, error);
+                        // The event may have been applied halfway, start over
+                        // from the last snapshot.
+                        let Ok(Some(mut snapshot)) = GameState::load_game(&pool, catalog).await
+                        else {
+                            tracing::error!("no snapshot to recover from, keeping the world");
+                            continue;
+                        };
+                        for logged in unsaved.drain(..) {
+                            let logged = EventData {
+                                event_idx: snapshot.next_event_idx,
+                                ..logged
+                            };
+                            if let Err(error) = snapshot.update(catalog, logged) {
+                                tracing::error!("failed to reapply event, {}", error);
+                            }
+                            snapshot.take_outcomes();
+                            snapshot.take_unlocked();
+                        }
+                        *state = snapshot;
+                        res_sender.send(Broadcast::Recovered(error)).ok();
+                        continue;
+                    }
+                    if matches!(
+                        event.event,
+                        Event::AddPlayer(_, _)
+                            | Event::EditPlayer(_, _)
+                            | Event::RestorePlayer(_, _)
+                            | Event::RemovePlayer(_)
+                            | Event::Connect(_)
+                            | Event::Disconnect(_)
+                    ) {
+                        unsaved.push(event.clone());
+                    }
+                    if let Some((user_id, transaction)) = restored {
+                        match GameState::store_restored(transaction, &state, user_id).await {
+                            Ok(()) => unsaved.clear(),
+                            // The farm stays archived too, it is only taken
+                            // out again if it is removed in the meantime.
+                            Err(error) => tracing::error!("failed to store the world, {}", error),
+                        }
+                    }
+                    let ticked = matches!(event.event, Event::Tick);
+                    filter.complete(catalog, &event, &mut state);
+                    res_sender
+                        .send(Broadcast::Event(event, Arc::new(filter)))
+                        .ok();
+                    for (user_id, achievement) in state.take_unlocked() {
+                        res_sender
+                            .send(Broadcast::Achievement(user_id, achievement))
+                            .ok();
                     }
+                    drop(state);
                     let state = &*game.read().await;
                     if state.time % SPEED == 0 {
-                        GameState::store_game(&pool, state).await;
+                        match GameState::store_game(&pool, state).await {
+                            Ok(()) => unsaved.clear(),
+                            Err(error) => tracing::error!("failed to store the world, {}", error),
+                        }
+                    }
+                    // Everything scheduled happens once per tick, not once
+                    // per event of that tick.
+                    if !ticked {
+                        continue;
+                    }
+                    for (user_id, truck_index) in state.arrivals() {
+                        req_sender
+                            .send(PartialEventData {
+                                event: Event::Arrive(user_id, truck_index),
+                                user_id: None,
+                            })
+                            .unwrap();
+                    }
+                    for user_id in policy.candidates(state) {
+                        req_sender
+                            .send(PartialEventData {
+                                event: Event::RemovePlayer(user_id),
+                                user_id: None,
+                            })
+                            .unwrap();
+                    }
+                    let mut history = history.write().await;
+                    history.sample(state);
+                    if state.time.is_multiple_of(BROADCAST_INTERVAL) {
+                        res_sender
+                            .send(Broadcast::Leaderboards(Arc::new(
+                                history.leaderboards(state),
+                            )))
+                            .ok();
                     }
                 }
             }
         });
 
-        GameState(game_state)
+        Ok(GameState(game_state))
     }
 
     pub async fn new_connection(
         &self,
         user_id: UserId,
     ) -> (
-        shared::State,
+        shared::View,
+        Catalog,
         mpsc::UnboundedSender<PartialEventData>,
-        broadcast::Receiver<EventData>,
+        broadcast::Receiver<Broadcast>,
     ) {
         (
-            self.0.state.read().await.view(user_id),
+            self.0.state.read().await.view(&self.0.catalog, user_id),
+            self.0.catalog.clone(),
             self.0.req_sender.clone(),
             self.0.res_sender.subscribe(),
         )
     }
 
+    pub async fn leaderboards(&self) -> Vec<Leaderboard> {
+        let state = self.0.state.read().await;
+        self.0.history.read().await.leaderboards(&state)
+    }
+
     pub fn add_player(&self, user_id: UserId, username: String) {
         self.0
             .req_sender
@@ -169,6 +400,51 @@ impl GameState {
             .unwrap();
     }
 
+    pub fn connect(&self, user_id: UserId) {
+        self.0
+            .req_sender
+            .send(PartialEventData {
+                event: Event::Connect(user_id),
+                user_id: None,
+            })
+            .unwrap();
+    }
+
+    pub fn disconnect(&self, user_id: UserId) {
+        self.0
+            .req_sender
+            .send(PartialEventData {
+                event: Event::Disconnect(user_id),
+                user_id: None,
+            })
+            .unwrap();
+    }
+
+    /// Brings back an archived farm, if the player has one. It stays in the
+    /// archive until the world is stored with it.
+    pub async fn restore_player(&self, pool: &SqlitePool, user_id: UserId) {
+        let player = match archive::load(pool, user_id).await {
+            Ok(player) => player,
+            Err(error) => {
+                tracing::error!("failed to load archived player {}, {}", user_id, error);
+                return;
+            }
+        };
+        if let Some(player) = player {
+            self.0
+                .req_sender
+                .send(PartialEventData {
+                    event: Event::RestorePlayer(user_id, Box::new(player)),
+                    user_id: None,
+                })
+                .unwrap();
+        }
+    }
+
+    pub fn resync(&self, user_id: UserId) {
+        self.0.res_sender.send(Broadcast::Resync(user_id)).ok();
+    }
+
     pub fn edit_player(&self, user_id: UserId, username: String) {
         self.0
             .req_sender
@@ -199,18 +475,29 @@ pub async fn ws_handler(
 
     if let Some((user_id,)) = result {
         Ok(ws.on_upgrade(move |socket: WebSocket| async move {
-            let (state, sender, mut receiver) = game_state.new_connection(user_id).await;
+            let (state, catalog, sender, mut receiver) = game_state.new_connection(user_id).await;
             let (mut sink, mut stream) = socket.split();
 
-            let msg = rmp_serde::to_vec(&shared::Res::Sync(SyncData {
+            let msg = rmp_serde::to_vec(&shared::Res::Sync(Box::new(SyncData {
                 user_id,
+                catalog,
                 state,
-            })).unwrap();
+            }))).unwrap();
 
             if sink.send(Message::Binary(msg)).await.is_err() {
                 return;
             }
 
+            let msg = rmp_serde::to_vec(&shared::Res::Leaderboards(game_state.leaderboards().await)).unwrap();
+            if sink.send(Message::Binary(msg)).await.is_err() {
+                return;
+            }
+
+            // The page may have been open since before the farm was archived.
+            game_state.restore_player(&pool, user_id).await;
+            // Catches up the farm in case the player was away.
+            game_state.connect(user_id);
+
             tokio::select!(
                 _ = async {
                     while let Some(msg) = stream.next().await {
@@ -223,6 +510,9 @@ pub async fn ws_handler(
                                             break;
                                         }
                                     }
+                                    shared::Req::Resync => {
+                                        game_state.resync(user_id);
+                                    }
                                 }
                             }
                         } else {
@@ -233,23 +523,73 @@ pub async fn ws_handler(
                 _ = async {
                     loop {
                         match receiver.recv().await {
-                            Ok(event) => {
-                                if event.filter(user_id) {
-                                    let msg = rmp_serde::to_vec(&shared::Res::Event(event)).unwrap();
+                            Ok(Broadcast::Event(event, filter)) => {
+                                let msg = rmp_serde::to_vec(&filter.res(&event, user_id)).unwrap();
+                                if sink.send(Message::Binary(msg)).await.is_err() {
+                                    break;
+                                }
+                            },
+                            Ok(Broadcast::Achievement(receiver_id, achievement)) => {
+                                if receiver_id == user_id {
+                                    let msg = rmp_serde::to_vec(&shared::Res::Achievement(achievement)).unwrap();
+                                    if sink.send(Message::Binary(msg)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            },
+                            Ok(Broadcast::Rejected(receiver_id, reason)) => {
+                                if receiver_id == user_id {
+                                    let msg = rmp_serde::to_vec(&shared::Res::Rejected(reason)).unwrap();
+                                    if sink.send(Message::Binary(msg)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            },
+                            Ok(Broadcast::Resync(receiver_id)) => {
+                                if receiver_id == user_id {
+                                    let (state, catalog, _, _) = game_state.new_connection(user_id).await;
+                                    let msg = rmp_serde::to_vec(&shared::Res::Sync(Box::new(SyncData {
+                                        user_id,
+                                        catalog,
+                                        state,
+                                    }))).unwrap();
                                     if sink.send(Message::Binary(msg)).await.is_err() {
                                         break;
                                     }
                                 }
                             },
+                            // Events since the snapshot are gone, everyone starts over.
+                            Ok(Broadcast::Recovered(error)) => {
+                                let msg = rmp_serde::to_vec(&shared::Res::Recovered(error)).unwrap();
+                                if sink.send(Message::Binary(msg)).await.is_err() {
+                                    break;
+                                }
+                                let (state, catalog, _, _) = game_state.new_connection(user_id).await;
+                                let msg = rmp_serde::to_vec(&shared::Res::Sync(Box::new(SyncData {
+                                    user_id,
+                                    catalog,
+                                    state,
+                                }))).unwrap();
+                                if sink.send(Message::Binary(msg)).await.is_err() {
+                                    break;
+                                }
+                            },
+                            Ok(Broadcast::Leaderboards(leaderboards)) => {
+                                let msg = rmp_serde::to_vec(&shared::Res::Leaderboards(leaderboards.to_vec())).unwrap();
+                                if sink.send(Message::Binary(msg)).await.is_err() {
+                                    break;
+                                }
+                            },
                             // If a broadcast message is discarded that wasn't seen yet by this receiver,
                             // request a full game state update.
                             Err(broadcast::error::RecvError::Lagged(_)) => {
-                                let (state, _, new_receiver) = game_state.new_connection(user_id).await;
+                                let (state, catalog, _, new_receiver) = game_state.new_connection(user_id).await;
                                 receiver = new_receiver;
-                                let msg = rmp_serde::to_vec(&shared::Res::Sync(SyncData {
+                                let msg = rmp_serde::to_vec(&shared::Res::Sync(Box::new(SyncData {
                                     user_id,
+                                    catalog,
                                     state,
-                                })).unwrap();
+                                }))).unwrap();
                                 if sink.send(Message::Binary(msg)).await.is_err() {
                                     break;
                                 }
@@ -261,6 +601,8 @@ pub async fn ws_handler(
                     }
                 } => {}
             );
+
+            game_state.disconnect(user_id);
         }))
     } else {
         Ok(Redirect::to("/login").into_response())
@@ -274,6 +616,7 @@ pub struct GameTemplate {}
 pub async fn get_game(
     session: ReadableSession,
     Extension(pool): Extension<SqlitePool>,
+    Extension(game_state): Extension<GameState>,
 ) -> Result<Response, ServerError> {
     let result: Option<(UserId,)> = sqlx::query_as(
         r#"
@@ -286,7 +629,8 @@ pub async fn get_game(
     .fetch_optional(&pool)
     .await?;
 
-    if let Some((_user_id,)) = result {
+    if let Some((user_id,)) = result {
+        game_state.restore_player(&pool, user_id).await;
         Ok(GameTemplate::default().into_response())
     } else {
         Ok(Redirect::to("/login").into_response())
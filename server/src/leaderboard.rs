@@ -0,0 +1,88 @@
+use askama::Template;
+use axum::Extension;
+use shared::{Leaderboard, Metric, State, Time, UserId, Window, ONE_HOUR, SPEED};
+use std::collections::{BTreeMap, VecDeque};
+use strum::IntoEnumIterator;
+
+use crate::game::GameState;
+
+/// Ticks between two samples of the metrics, the rolling windows are only
+/// as precise as this.
+pub const SAMPLE_INTERVAL: Time = ONE_HOUR * SPEED;
+/// Ticks between two leaderboard broadcasts to the players.
+pub const BROADCAST_INTERVAL: Time = 60 * SPEED;
+
+/// The value of every metric for every player at one point in time.
+type Sample = BTreeMap<Metric, BTreeMap<UserId, u64>>;
+
+/// Past values of every metric for the rolling windows. It lives in memory
+/// only, so the windows start over when the server restarts.
+#[derive(Default)]
+pub struct History {
+    samples: VecDeque<(Time, Sample)>,
+}
+
+impl History {
+    pub fn sample(&mut self, state: &State) {
+        if !state.time.is_multiple_of(SAMPLE_INTERVAL) {
+            return;
+        }
+
+        let sample = Metric::iter()
+            .map(|metric| {
+                let values = state
+                    .players
+                    .iter()
+                    .map(|(user_id, player)| (*user_id, metric.of(&player.farm)))
+                    .collect();
+                (metric, values)
+            })
+            .collect();
+        self.samples.push_back((state.time, sample));
+
+        let longest = Window::iter().filter_map(Window::length).max().unwrap_or(0);
+        while self
+            .samples
+            .front()
+            .is_some_and(|(time, _)| time + longest < state.time)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The oldest sample still inside the window.
+    fn baseline(
+        &self,
+        metric: Metric,
+        window: Window,
+        time: Time,
+    ) -> Option<&BTreeMap<UserId, u64>> {
+        let start = time.saturating_sub(window.length()?);
+        self.samples
+            .iter()
+            .find(|(sampled, _)| *sampled >= start)
+            .and_then(|(_, sample)| sample.get(&metric))
+    }
+
+    pub fn leaderboards(&self, state: &State) -> Vec<Leaderboard> {
+        Metric::iter()
+            .flat_map(|metric| Window::iter().map(move |window| (metric, window)))
+            .map(|(metric, window)| {
+                let baseline = self.baseline(metric, window, state.time);
+                state.leaderboard(metric, window, baseline)
+            })
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "leaderboards.html")]
+pub struct LeaderboardsTemplate {
+    leaderboards: Vec<Leaderboard>,
+}
+
+pub async fn get_leaderboards(Extension(game_state): Extension<GameState>) -> LeaderboardsTemplate {
+    LeaderboardsTemplate {
+        leaderboards: game_state.leaderboards().await,
+    }
+}
@@ -1,9 +1,13 @@
 mod about;
+mod archive;
 mod auth;
+mod catalog;
 mod db;
 mod error;
 mod game;
 mod index;
+mod leaderboard;
+mod refill;
 
 use error::*;
 
@@ -24,6 +28,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
     let pool = db::setup().await?;
+    let catalog = catalog::load()?;
 
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
@@ -41,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_secure(false)
         .with_http_only(false);
 
-    let game_state = game::GameState::new(pool.clone()).await;
+    let game_state = game::GameState::new(pool.clone(), catalog).await?;
 
     // build our application with some routes
     let app = Router::new()
@@ -57,6 +62,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .route("/", get(index::get_index))
         .route("/about", get(about::get_about))
+        .route("/leaderboards", get(leaderboard::get_leaderboards))
         .route("/game/ws", get(game::ws_handler))
         .route("/game", get(game::get_game))
         .route("/game/*subpath", get(game::get_game))
@@ -0,0 +1,12 @@
+use shared::{RefillSchedule, Time, SPEED};
+
+/// Reads the refill schedule from `REFILL_INTERVAL`, the seconds between two
+/// refills. Which veggies are refilled is up to the weights in the catalog.
+pub fn from_env() -> RefillSchedule {
+    std::env::var("REFILL_INTERVAL")
+        .ok()
+        .and_then(|seconds| seconds.parse::<Time>().ok())
+        .map_or_else(RefillSchedule::default, |seconds| RefillSchedule {
+            interval: seconds * SPEED,
+        })
+}
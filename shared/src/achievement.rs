@@ -0,0 +1,76 @@
+use crate::{Farm, Money, Quantity, State, UserId};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, IntoEnumIterator};
+
+/// Running totals of what a farm has done, kept for achievements.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Stats {
+    pub harvests: u64,
+    pub harvested: Quantity,
+    pub trades: u64,
+    pub contracts: u64,
+}
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq, Ord, PartialOrd,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Achievement {
+    FirstHarvest,
+    HundredVeggies,
+    FirstTrade,
+    HundredTrades,
+    FirstContract,
+    FiveFields,
+    Wealthy,
+}
+
+impl Achievement {
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::FirstHarvest => "Harvest a field",
+            Achievement::HundredVeggies => "Harvest 100 veggies",
+            Achievement::FirstTrade => "Complete a trade",
+            Achievement::HundredTrades => "Complete 100 trades",
+            Achievement::FirstContract => "Deliver a contract",
+            Achievement::FiveFields => "Own 5 fields",
+            Achievement::Wealthy => "Have 1000 money",
+        }
+    }
+
+    pub fn is_reached(self, farm: &Farm) -> bool {
+        const WEALTHY: Money = 1000;
+
+        match self {
+            Achievement::FirstHarvest => farm.stats.harvests >= 1,
+            Achievement::HundredVeggies => farm.stats.harvested >= 100,
+            Achievement::FirstTrade => farm.stats.trades >= 1,
+            Achievement::HundredTrades => farm.stats.trades >= 100,
+            Achievement::FirstContract => farm.stats.contracts >= 1,
+            Achievement::FiveFields => farm.fields.len() >= 5,
+            Achievement::Wealthy => farm.money >= WEALTHY,
+        }
+    }
+}
+
+impl State {
+    /// Unlocks everything players reached with the last event.
+    pub(crate) fn check_achievements(&mut self) {
+        for (user_id, player) in &mut self.players {
+            for achievement in Achievement::iter() {
+                if !player.achievements.contains(&achievement)
+                    && achievement.is_reached(&player.farm)
+                {
+                    player.achievements.insert(achievement);
+                    self.unlocked.push((*user_id, achievement));
+                }
+            }
+        }
+    }
+
+    /// Achievements unlocked since the last call, so that the players can be
+    /// notified.
+    pub fn take_unlocked(&mut self) -> Vec<(UserId, Achievement)> {
+        std::mem::take(&mut self.unlocked)
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Money, Season, Time, Veggie, SPEED};
+use rand::{rngs::SmallRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+/// Everything there is to know about a kind of veggie.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VeggieSpec {
+    pub id: Veggie,
+    pub name: String,
+    /// Relative chance of the veggie being refilled into a silo.
+    pub weight: u64,
+    /// Seconds from planting until the crop is ripe.
+    pub growth_time: Time,
+    pub base_value: Money,
+    /// File name of the sprite in the assets directory.
+    pub sprite: String,
+    /// Seasons the veggie grows well in, any season if empty.
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+}
+
+impl VeggieSpec {
+    /// Ticks from planting until the crop is ripe.
+    pub fn growth_ticks(&self) -> Time {
+        self.growth_time * SPEED
+    }
+
+    pub fn prefers(&self, season: Season) -> bool {
+        self.seasons.is_empty() || self.seasons.contains(&season)
+    }
+}
+
+/// The veggies the world knows about. The server loads it from a file and
+/// sends it along with every sync.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Catalog {
+    pub veggies: Vec<VeggieSpec>,
+}
+
+impl Catalog {
+    pub fn get(&self, veggie: Veggie) -> Option<&VeggieSpec> {
+        self.veggies.iter().find(|spec| spec.id == veggie)
+    }
+
+    pub fn contains(&self, veggie: Veggie) -> bool {
+        self.get(veggie).is_some()
+    }
+
+    pub fn name(&self, veggie: Veggie) -> &str {
+        self.get(veggie).map_or("Unknown", |spec| &spec.name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Veggie> + '_ {
+        self.veggies.iter().map(|spec| spec.id)
+    }
+
+    pub fn choose(&self, rng: &mut SmallRng) -> Option<Veggie> {
+        self.veggies
+            .choose_weighted(rng, |spec| spec.weight)
+            .ok()
+            .map(|spec| spec.id)
+    }
+
+    pub fn validate(&self) -> Result<(), CatalogError> {
+        if self.veggies.is_empty() {
+            return Err(CatalogError::Empty);
+        }
+
+        let mut ids = BTreeSet::new();
+        for spec in &self.veggies {
+            if !ids.insert(spec.id) {
+                return Err(CatalogError::DuplicateId(spec.id));
+            }
+            if spec.weight == 0 {
+                return Err(CatalogError::ZeroWeight(spec.id));
+            }
+            if spec.growth_time == 0 {
+                return Err(CatalogError::ZeroGrowthTime(spec.id));
+            }
+            if spec.base_value == 0 {
+                return Err(CatalogError::ZeroBaseValue(spec.id));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CatalogError {
+    Empty,
+    DuplicateId(Veggie),
+    ZeroWeight(Veggie),
+    ZeroGrowthTime(Veggie),
+    ZeroBaseValue(Veggie),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Empty => write!(f, "the catalog contains no veggies"),
+            CatalogError::DuplicateId(Veggie(id)) => write!(f, "veggie id {} is used twice", id),
+            CatalogError::ZeroWeight(Veggie(id)) => write!(f, "veggie {} has a weight of zero", id),
+            CatalogError::ZeroGrowthTime(Veggie(id)) => {
+                write!(f, "veggie {} has a growth time of zero", id)
+            }
+            CatalogError::ZeroBaseValue(Veggie(id)) => {
+                write!(f, "veggie {} has a base value of zero", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
@@ -0,0 +1,76 @@
+use crate::{check, Audience, Redaction, RejectReason, State, Time, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+pub const MAX_MESSAGE_LENGTH: usize = 200;
+/// Messages kept in the history, older ones are dropped.
+pub const CHAT_HISTORY: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Channel {
+    Global,
+    Direct(UserId),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub from: UserId,
+    pub channel: Channel,
+    pub text: String,
+    pub time: Time,
+}
+
+impl ChatMessage {
+    /// Direct messages are only seen by the sender and the recipient.
+    pub fn is_visible_to(&self, receiver: UserId) -> bool {
+        match self.channel {
+            Channel::Global => true,
+            Channel::Direct(to) => to == receiver || self.from == receiver,
+        }
+    }
+}
+
+pub fn is_valid_message(text: &str) -> bool {
+    !text.trim().is_empty()
+        && text.chars().count() <= MAX_MESSAGE_LENGTH
+        && !text.chars().any(char::is_control)
+}
+
+impl State {
+    pub fn send_message(
+        &mut self,
+        from: UserId,
+        channel: Channel,
+        text: String,
+    ) -> Result<(), RejectReason> {
+        check(is_valid_message(&text), RejectReason::InvalidMessage)?;
+        if let Channel::Direct(to) = channel {
+            check(
+                to != from && self.players.contains_key(&to),
+                RejectReason::UnknownPlayer,
+            )?;
+        }
+
+        let message = ChatMessage {
+            from,
+            channel,
+            text: text.trim().to_string(),
+            time: self.time,
+        };
+        let audience = match channel {
+            Channel::Global => Audience::Everyone,
+            Channel::Direct(to) => Audience::Only(BTreeSet::from([from, to])),
+        };
+        self.tell(audience, Redaction::Message(message.clone()));
+        self.post(message);
+
+        Ok(())
+    }
+
+    pub(crate) fn post(&mut self, message: ChatMessage) {
+        self.chat.push_back(message);
+        while self.chat.len() > CHAT_HISTORY {
+            self.chat.pop_front();
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    check, Audience, Catalog, Money, Quantity, Redaction, RejectReason, State, Time, Trip, UserId,
+    Veggie, VeggieQty, ONE_MINUTE, SPEED, TRAVEL_TIME,
+};
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+
+pub type ContractId = u64;
+
+pub const CONTRACT_INTERVAL: Time = 5 * ONE_MINUTE * SPEED;
+pub const CONTRACT_DURATION: Time = 20 * ONE_MINUTE * SPEED;
+pub const MAX_CONTRACTS: usize = 5;
+pub const MAX_CONTRACT_QTY: Quantity = 3;
+
+/// An order from the town asking for veggies by a deadline. The first
+/// player to send a truck takes it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Contract {
+    pub veggie: Veggie,
+    pub qty: Quantity,
+    /// Paid on delivery, on top of the market value.
+    pub reward: Money,
+    pub deadline: Time,
+    /// Set once a truck is on its way.
+    pub taken_by: Option<UserId>,
+}
+
+impl State {
+    /// Sends a truck with the requested veggies to the town. It must arrive
+    /// before the deadline.
+    pub fn deliver_contract(
+        &mut self,
+        user_id: UserId,
+        truck_index: usize,
+        contract_id: ContractId,
+    ) -> Result<(), RejectReason> {
+        let contract = self
+            .contracts
+            .get(&contract_id)
+            .ok_or(RejectReason::NoSuchContract)?;
+        check(contract.taken_by.is_none(), RejectReason::ContractTaken)?;
+        check(
+            self.time + TRAVEL_TIME <= contract.deadline,
+            RejectReason::TooLate,
+        )?;
+        let truck = self
+            .farm(user_id)
+            .ok_or(RejectReason::UnknownPlayer)?
+            .loaded_truck(truck_index)?;
+        check(
+            truck
+                .veggies()
+                .is_some_and(|v| v.veggie() == contract.veggie && v.qty() >= contract.qty),
+            RejectReason::WrongCargo,
+        )?;
+
+        let trip = Trip::Delivery {
+            contract: contract_id,
+        };
+        let truck = &mut self.players.get_mut(&user_id).unwrap().farm.trucks[truck_index];
+        truck.dispatch(trip)?;
+        if let Some(contract) = self.contracts.get_mut(&contract_id) {
+            contract.taken_by = Some(user_id);
+        }
+        self.tell(
+            Audience::Everyone,
+            Redaction::Dispatched {
+                user_id,
+                truck: truck_index,
+                trip,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Hands over the veggies of a truck that arrived in town and pays for
+    /// them.
+    pub(crate) fn complete_contract(
+        &mut self,
+        catalog: &Catalog,
+        user_id: UserId,
+        truck_index: usize,
+        contract_id: ContractId,
+    ) {
+        let Some(contract) = self.contracts.remove(&contract_id) else {
+            return;
+        };
+
+        if let Some(farm) = self.players.get_mut(&user_id).map(|p| &mut p.farm) {
+            if let Some(veggies) = farm
+                .trucks
+                .get_mut(truck_index)
+                .and_then(|t| t.veggies.as_mut())
+            {
+                let delivered = veggies.take(contract.qty);
+                if veggies.is_empty() {
+                    farm.trucks[truck_index].veggies = None;
+                }
+
+                let value = self.prices.value(catalog, &delivered);
+                farm.money = farm
+                    .money
+                    .saturating_add(value)
+                    .saturating_add(contract.reward);
+                farm.stats.contracts += 1;
+            }
+        }
+
+        // Trucks set off with at least what the contract asks for and their
+        // cargo can't change on the way, so everyone can tell what was sold.
+        self.prices.sell(catalog, contract.veggie, contract.qty);
+    }
+
+    /// Posts a new contract now and then and drops those no one took in time.
+    pub(crate) fn update_contracts(&mut self, catalog: &Catalog, rng: &mut SmallRng) {
+        let time = self.time;
+        self.contracts
+            .retain(|_, contract| contract.taken_by.is_some() || contract.deadline > time);
+
+        if !time.is_multiple_of(CONTRACT_INTERVAL) || self.contracts.len() >= MAX_CONTRACTS {
+            return;
+        }
+        let Some(veggie) = catalog.choose(rng) else {
+            return;
+        };
+
+        let qty = rng.gen_range(1..=MAX_CONTRACT_QTY);
+        let reward = self
+            .prices
+            .value(catalog, &VeggieQty::new(veggie, qty))
+            .saturating_add(1)
+            / 2;
+        self.contracts.insert(
+            self.next_contract_id,
+            Contract {
+                veggie,
+                qty,
+                reward,
+                deadline: time + CONTRACT_DURATION,
+                taken_by: None,
+            },
+        );
+        self.next_contract_id += 1;
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{check, Audience, Catalog, Farm, Redaction, RejectReason, Silo, State, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use strum::Display;
+
+pub type CoopId = u64;
+
+pub const MAX_COOP_NAME: usize = 24;
+pub const MAX_MEMBERS: usize = 8;
+pub const COOP_STORAGE: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "title_case")]
+pub enum Role {
+    /// May kick members, handed on to the longest member when leaving.
+    Leader,
+    Member,
+}
+
+/// A group of players sharing a silo that every member can deposit into
+/// and plant from.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Coop {
+    pub name: String,
+    pub members: BTreeMap<UserId, Role>,
+    /// Members in the order they joined, the first one leads.
+    pub seniority: Vec<UserId>,
+    pub silo: Silo,
+}
+
+impl Coop {
+    pub fn is_member(&self, user_id: UserId) -> bool {
+        self.members.contains_key(&user_id)
+    }
+}
+
+impl State {
+    pub fn create_coop(&mut self, user_id: UserId, name: String) -> Result<(), RejectReason> {
+        let name = name.trim().to_string();
+        check(
+            !name.is_empty() && name.chars().count() <= MAX_COOP_NAME,
+            RejectReason::InvalidName,
+        )?;
+        check(
+            self.coops.values().all(|c| c.name != name),
+            RejectReason::NameTaken,
+        )?;
+        let player = self
+            .players
+            .get_mut(&user_id)
+            .ok_or(RejectReason::UnknownPlayer)?;
+        check(player.coop.is_none(), RejectReason::AlreadyInCoop)?;
+        player.coop = Some(self.next_coop_id);
+
+        self.coops.insert(
+            self.next_coop_id,
+            Coop {
+                name,
+                members: BTreeMap::from([(user_id, Role::Leader)]),
+                seniority: vec![user_id],
+                silo: Silo {
+                    storage: VecDeque::new(),
+                    max_storage: COOP_STORAGE,
+                    level: 0,
+                },
+            },
+        );
+        self.tell_coop(self.next_coop_id);
+        self.next_coop_id += 1;
+
+        Ok(())
+    }
+
+    pub fn join_coop(&mut self, user_id: UserId, coop_id: CoopId) -> Result<(), RejectReason> {
+        let coop = self
+            .coops
+            .get_mut(&coop_id)
+            .ok_or(RejectReason::NoSuchCoop)?;
+        check(coop.members.len() < MAX_MEMBERS, RejectReason::CoopFull)?;
+        let player = self
+            .players
+            .get_mut(&user_id)
+            .ok_or(RejectReason::UnknownPlayer)?;
+        check(player.coop.is_none(), RejectReason::AlreadyInCoop)?;
+
+        player.coop = Some(coop_id);
+        coop.members.insert(user_id, Role::Member);
+        coop.seniority.push(user_id);
+        self.tell_coop(coop_id);
+
+        Ok(())
+    }
+
+    /// Leaves the co-op of a player. The last one to leave takes the shared
+    /// silo down with them.
+    pub fn leave_coop(&mut self, user_id: UserId) -> Result<(), RejectReason> {
+        let player = self
+            .players
+            .get_mut(&user_id)
+            .ok_or(RejectReason::UnknownPlayer)?;
+        let coop_id = player.coop.ok_or(RejectReason::NotInCoop)?;
+        let coop = self
+            .coops
+            .get_mut(&coop_id)
+            .ok_or(RejectReason::NotInCoop)?;
+
+        player.coop = None;
+        coop.members.remove(&user_id);
+        coop.seniority.retain(|member| *member != user_id);
+        match coop.seniority.first() {
+            Some(next) => {
+                coop.members.insert(*next, Role::Leader);
+            }
+            None => {
+                self.coops.remove(&coop_id);
+            }
+        }
+        self.tell_coop(coop_id);
+
+        Ok(())
+    }
+
+    /// Removes a member from the co-op, only the leader may do so.
+    pub fn kick_member(&mut self, user_id: UserId, member: UserId) -> Result<(), RejectReason> {
+        let coop = self.coop_of(user_id).ok_or(RejectReason::NotInCoop)?;
+        check(
+            coop.members.get(&user_id) == Some(&Role::Leader),
+            RejectReason::NotLeader,
+        )?;
+        check(
+            member != user_id && coop.is_member(member),
+            RejectReason::NoSuchMember,
+        )?;
+
+        self.leave_coop(member)
+    }
+
+    /// Moves the first veggies of one of the player's silos into the shared
+    /// silo.
+    pub fn deposit(&mut self, user_id: UserId, silo_index: usize) -> Result<(), RejectReason> {
+        let (coop, farm) = self.coop_and_farm(user_id)?;
+        check(
+            coop.silo.storage.len() < coop.silo.max_storage,
+            RejectReason::CoopSiloFull,
+        )?;
+        let silo = farm
+            .silos
+            .get_mut(silo_index)
+            .ok_or(RejectReason::NoSuchBuilding)?;
+        let veggies = silo.storage.pop_front().ok_or(RejectReason::SiloEmpty)?;
+        coop.silo.storage.push_back(veggies);
+        self.tell_members(user_id);
+
+        Ok(())
+    }
+
+    pub fn plant_from_coop(
+        &mut self,
+        user_id: UserId,
+        catalog: &Catalog,
+    ) -> Result<(), RejectReason> {
+        let (coop, farm) = self.coop_and_farm(user_id)?;
+        farm.plant_from(&mut coop.silo.storage, catalog)?;
+        self.tell_members(user_id);
+
+        Ok(())
+    }
+
+    /// Tells everyone how a co-op stands now, only members see its silo.
+    fn tell_coop(&mut self, coop_id: CoopId) {
+        let Some(coop) = self.coops.get(&coop_id) else {
+            self.tell(Audience::Everyone, Redaction::Coop(coop_id, None));
+            return;
+        };
+        let members: BTreeSet<UserId> = coop.members.keys().copied().collect();
+        let mut public = coop.clone();
+        public.silo.storage.clear();
+        let redaction = Redaction::Coop(coop_id, Some(coop.clone()));
+        self.tell(Audience::Only(members.clone()), redaction);
+        self.tell(
+            Audience::AllBut(members),
+            Redaction::Coop(coop_id, Some(public)),
+        );
+    }
+
+    /// Tells the members of a player's co-op what its silo holds now.
+    fn tell_members(&mut self, user_id: UserId) {
+        let Some((coop_id, coop)) = self
+            .players
+            .get(&user_id)
+            .and_then(|p| p.coop)
+            .and_then(|coop_id| self.coops.get(&coop_id).map(|coop| (coop_id, coop)))
+        else {
+            return;
+        };
+        let members = coop.members.keys().copied().collect();
+        let redaction = Redaction::Coop(coop_id, Some(coop.clone()));
+        self.tell(Audience::Only(members), redaction);
+    }
+
+    fn coop_and_farm(&mut self, user_id: UserId) -> Result<(&mut Coop, &mut Farm), RejectReason> {
+        let player = self
+            .players
+            .get_mut(&user_id)
+            .ok_or(RejectReason::UnknownPlayer)?;
+        let coop = player
+            .coop
+            .and_then(|coop_id| self.coops.get_mut(&coop_id))
+            .ok_or(RejectReason::NotInCoop)?;
+        Ok((coop, &mut player.farm))
+    }
+
+    pub fn coop_of(&self, user_id: UserId) -> Option<&Coop> {
+        self.players
+            .get(&user_id)
+            .and_then(|p| p.coop)
+            .and_then(|coop_id| self.coops.get(&coop_id))
+    }
+}
@@ -0,0 +1,409 @@
+use crate::{
+    Catalog, ChatMessage, Coop, CoopId, Event, EventData, Fill, OfferId, Order, OrderId,
+    PlayerSummary, Quantity, Res, State, SyncData, TradeOffer, Trip, Truck, UserId, Veggie,
+    VeggieQty,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// What came out of an event, told to players who may not see the event or
+/// couldn't replay it on their view.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Redaction {
+    /// A player joined, came back or was renamed.
+    Player(UserId, PlayerSummary),
+    /// A player was removed, along with their offers and orders.
+    Removed(UserId),
+    /// A player connected or left.
+    Online(UserId, bool),
+    /// Veggies were harvested, which moves their price.
+    Harvested(VeggieQty),
+    /// A player bought a truck, trucks are public.
+    TruckBought(UserId),
+    /// A trade offer was made, or taken off once it was answered.
+    Offer(OfferId, Option<TradeOffer>),
+    /// A truck set off with cargo only its owner knows.
+    Dispatched {
+        user_id: UserId,
+        truck: usize,
+        trip: Trip,
+    },
+    /// A truck is done with its trip.
+    Arrived {
+        user_id: UserId,
+        truck: usize,
+        trip: Trip,
+    },
+    /// A truck came to trade this cargo with a truck of the receiver.
+    Visited {
+        user_id: UserId,
+        truck: usize,
+        cargo: VeggieQty,
+    },
+    /// A truck of the receiver takes this cargo home from a trade.
+    Traded {
+        user_id: UserId,
+        truck: usize,
+        cargo: Option<VeggieQty>,
+        traded: bool,
+    },
+    /// An order was placed, partly filled or dropped from the book.
+    Order(OrderId, Option<Order>),
+    /// An order of the receiver was filled.
+    Filled(Fill),
+    /// Veggies changed hands on the market, which moves their price.
+    Sold(Veggie, Quantity),
+    /// A co-op as it stands now, the silo shows only for members.
+    Coop(CoopId, Option<Coop>),
+    Message(ChatMessage),
+}
+
+/// Who an outcome is told to.
+#[derive(Debug, Clone)]
+pub enum Audience {
+    Everyone,
+    Only(BTreeSet<UserId>),
+    AllBut(BTreeSet<UserId>),
+}
+
+impl Audience {
+    pub fn only(user_id: UserId) -> Self {
+        Audience::Only(BTreeSet::from([user_id]))
+    }
+
+    pub fn includes(&self, user_id: UserId) -> bool {
+        match self {
+            Audience::Everyone => true,
+            Audience::Only(user_ids) => user_ids.contains(&user_id),
+            Audience::AllBut(user_ids) => !user_ids.contains(&user_id),
+        }
+    }
+}
+
+/// Who gets to see an event as it is, and what the others are told once it
+/// is applied.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Players who receive the event as it is, everyone if `None`.
+    pub audience: Option<BTreeSet<UserId>>,
+    /// What all others are told instead, in the order it came about. The
+    /// event is skipped for those who are told nothing.
+    pub outcomes: Vec<(Audience, Redaction)>,
+    /// A fresh view for the one receiver who couldn't replay the event at
+    /// all.
+    pub sync: Option<Box<SyncData>>,
+}
+
+impl Filter {
+    /// Adds what came out of the event, once it is applied to the state.
+    pub fn complete(&mut self, catalog: &Catalog, event: &EventData, state: &mut State) {
+        self.outcomes.extend(state.take_outcomes());
+        // A restored farm catches up on ticks only the server can roll.
+        if let Event::RestorePlayer(user_id, _) = event.event {
+            self.sync = Some(Box::new(SyncData {
+                user_id,
+                catalog: catalog.clone(),
+                state: state.view(catalog, user_id),
+            }));
+        }
+    }
+
+    pub fn res(&self, event: &EventData, receiver: UserId) -> Res {
+        if let Some(sync) = self.sync.as_ref().filter(|sync| sync.user_id == receiver) {
+            return Res::Sync(sync.clone());
+        }
+        if self
+            .audience
+            .as_ref()
+            .is_none_or(|audience| audience.contains(&receiver))
+        {
+            return Res::Event(event.clone());
+        }
+
+        let redactions: Vec<Redaction> = self
+            .outcomes
+            .iter()
+            .filter(|(audience, _)| audience.includes(receiver))
+            .map(|(_, redaction)| redaction.clone())
+            .collect();
+        if redactions.is_empty() {
+            Res::Skip(event.event_idx)
+        } else {
+            Res::Event(EventData {
+                event: Event::Redacted(redactions),
+                // Other players aren't part of the receiver's view.
+                user_id: event.user_id.filter(|user_id| *user_id == receiver),
+                ..event.clone()
+            })
+        }
+    }
+}
+
+impl EventData {
+    /// Views hold no other player, so only ticks and events concerning
+    /// nobody but the receiver are replayed as they are. The outcomes are
+    /// added once the event is applied, see [`Filter::complete`].
+    pub fn filter(&self) -> Filter {
+        let only = |user_id| Filter {
+            audience: Some(BTreeSet::from([user_id])),
+            ..Filter::default()
+        };
+        let nobody = Filter {
+            audience: Some(BTreeSet::new()),
+            ..Filter::default()
+        };
+
+        match (&self.event, self.user_id) {
+            (Event::Tick, None) => Filter::default(),
+            (
+                Event::AddPlayer(user_id, _)
+                | Event::EditPlayer(user_id, _)
+                | Event::Connect(user_id)
+                | Event::Disconnect(user_id),
+                None,
+            ) => only(*user_id),
+            // These concern other players, depend on the cargo of trucks or
+            // fill orders from farms no one but their owner can see.
+            (
+                Event::ProposeTrade(_, _, _)
+                | Event::AcceptTrade(_)
+                | Event::DeliverContract(_, _)
+                | Event::PlaceOrder(_, _, _, _)
+                | Event::CreateCoop(_)
+                | Event::JoinCoop(_)
+                | Event::LeaveCoop
+                | Event::KickMember(_)
+                | Event::SendMessage(_, _),
+                Some(_),
+            ) => nobody,
+            (_, Some(user_id)) => only(user_id),
+            (_, None) => nobody,
+        }
+    }
+}
+
+impl State {
+    /// Replays an outcome, without the farms of others.
+    pub(crate) fn apply_redaction(&mut self, catalog: &Catalog, redaction: Redaction) {
+        match redaction {
+            // Views sum up other players themselves.
+            Redaction::Player(_, _) | Redaction::Online(_, _) => {}
+            Redaction::Removed(user_id) => {
+                self.remove_player(user_id);
+            }
+            Redaction::Harvested(veggies) => {
+                self.prices.harvest(catalog, &veggies);
+            }
+            Redaction::TruckBought(user_id) => {
+                if let Some(farm) = self.farm_mut(user_id) {
+                    farm.trucks.push(Truck::new());
+                }
+            }
+            Redaction::Offer(offer_id, Some(offer)) => {
+                self.offers.insert(offer_id, offer);
+                self.next_offer_id = self.next_offer_id.max(offer_id + 1);
+            }
+            Redaction::Offer(offer_id, None) => {
+                self.offers.remove(&offer_id);
+            }
+            Redaction::Dispatched {
+                user_id,
+                truck,
+                trip,
+            } => {
+                if let Some(truck) = self
+                    .farm_mut(user_id)
+                    .and_then(|farm| farm.trucks.get_mut(truck))
+                {
+                    truck.set_off(trip);
+                }
+                if let Trip::Delivery { contract } = trip {
+                    if let Some(contract) = self.contracts.get_mut(&contract) {
+                        contract.taken_by = Some(user_id);
+                    }
+                }
+            }
+            Redaction::Arrived {
+                user_id,
+                truck,
+                trip,
+            } => {
+                self.finish_trip(catalog, user_id, truck, trip);
+            }
+            Redaction::Visited {
+                user_id,
+                truck,
+                cargo,
+            } => {
+                if let Some(farm) = self.farm_mut(user_id) {
+                    farm.host_trade(truck, cargo, catalog);
+                }
+            }
+            Redaction::Traded {
+                user_id,
+                truck,
+                cargo,
+                traded,
+            } => {
+                if let Some(farm) = self.farm_mut(user_id) {
+                    farm.return_from_trade(truck, cargo, traded);
+                }
+            }
+            Redaction::Order(order_id, Some(order)) => {
+                self.market.orders.insert(order_id, order);
+                self.market.next_order_id = self.market.next_order_id.max(order_id + 1);
+            }
+            Redaction::Order(order_id, None) => {
+                self.market.orders.remove(&order_id);
+            }
+            Redaction::Filled(fill) => {
+                self.settle(catalog, fill);
+            }
+            Redaction::Sold(veggie, qty) => {
+                self.prices.sell(catalog, veggie, qty);
+            }
+            Redaction::Coop(coop_id, coop) => {
+                for player in self.players.values_mut() {
+                    if player.coop == Some(coop_id) {
+                        player.coop = None;
+                    }
+                }
+                match coop {
+                    Some(coop) => {
+                        for user_id in coop.members.keys() {
+                            if let Some(player) = self.players.get_mut(user_id) {
+                                player.coop = Some(coop_id);
+                            }
+                        }
+                        self.coops.insert(coop_id, coop);
+                        self.next_coop_id = self.next_coop_id.max(coop_id + 1);
+                    }
+                    None => {
+                        self.coops.remove(&coop_id);
+                    }
+                }
+            }
+            Redaction::Message(message) => {
+                self.post(message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_player, broadcast, catalog, stock, wait, ALICE, BOB, CARROT};
+    use crate::{Channel, Contract, Field, Side, View, TRAVEL_TIME};
+
+    const CAROL: UserId = 3;
+
+    fn play(
+        state: &mut State,
+        views: &mut [View],
+        catalog: &Catalog,
+        user_id: UserId,
+        event: Event,
+    ) {
+        state.validate(catalog, &event, user_id).unwrap();
+        let event = EventData {
+            event,
+            user_id: Some(user_id),
+            seed: 0,
+            event_idx: state.next_event_idx,
+        };
+        broadcast(state, views, catalog, event);
+    }
+
+    #[test]
+    fn views_replay_what_they_are_told() {
+        let catalog = catalog();
+        let mut state = State {
+            secret_seed: 7,
+            ..State::default()
+        };
+        for (user_id, username) in [(ALICE, "alice"), (BOB, "bob"), (CAROL, "carol")] {
+            let farm = add_player(&mut state, &catalog, user_id, username);
+            farm.money = 100;
+            farm.fields.push(Field::new());
+            farm.trucks.push(Truck::new());
+            stock(&mut state, user_id, CARROT, 6);
+        }
+        state.contracts.insert(
+            0,
+            Contract {
+                veggie: CARROT,
+                qty: 1,
+                reward: 10,
+                deadline: TRAVEL_TIME * 2,
+                taken_by: None,
+            },
+        );
+        let mut views: Vec<View> = [ALICE, BOB, CAROL]
+            .into_iter()
+            .map(|user_id| state.view(&catalog, user_id))
+            .collect();
+
+        for (user_id, event) in [
+            (ALICE, Event::LoadTruck(0)),
+            (BOB, Event::LoadTruck(0)),
+            (CAROL, Event::LoadTruck(0)),
+            (ALICE, Event::PlantField(0)),
+            (ALICE, Event::ProposeTrade(0, BOB, 0)),
+            (BOB, Event::ProposeTrade(0, CAROL, 0)),
+            (BOB, Event::AcceptTrade(0)),
+            (CAROL, Event::DeclineTrade(1)),
+            (CAROL, Event::DeliverContract(0, 0)),
+            (
+                CAROL,
+                Event::SendMessage(Channel::Direct(ALICE), "hi".to_string()),
+            ),
+            (
+                BOB,
+                Event::SendMessage(Channel::Global, "hello".to_string()),
+            ),
+            (ALICE, Event::PlaceOrder(Side::Sell, CARROT, 1, 5)),
+            (CAROL, Event::PlaceOrder(Side::Buy, CARROT, 2, 6)),
+            (BOB, Event::CreateCoop("bobs".to_string())),
+            (CAROL, Event::JoinCoop(0)),
+            (CAROL, Event::Deposit(0)),
+        ] {
+            play(&mut state, &mut views, &catalog, user_id, event);
+        }
+        // Alice's crop ripens in a few ticks.
+        let harvest = Event::HarvestField(0);
+        while state.validate(&catalog, &harvest, ALICE).is_err() {
+            wait(&mut state, &mut views, &catalog, 1);
+        }
+        play(&mut state, &mut views, &catalog, ALICE, harvest);
+        wait(&mut state, &mut views, &catalog, TRAVEL_TIME * 3);
+        play(
+            &mut state,
+            &mut views,
+            &catalog,
+            BOB,
+            Event::KickMember(CAROL),
+        );
+
+        // The sequence went the whole way.
+        assert_eq!(state.market.fills.len(), 1);
+        assert!(state.contracts.is_empty());
+        assert_eq!(state.farm(ALICE).unwrap().stats.trades, 2);
+        assert!(state.farm(ALICE).unwrap().trucks[0].is_ready());
+
+        for view in &views {
+            let expected = state.view(&catalog, view.receiver());
+            let parts = |view: &View| {
+                format!(
+                    "{:?} {:?} {:?} {:?} {:?}",
+                    view.me().unwrap().farm,
+                    view.offers(),
+                    view.market().orders,
+                    view.prices(),
+                    view.coops(),
+                )
+            };
+            assert_eq!(parts(view), parts(&expected));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    Achievement, Catalog, ChatMessage, Climate, Contract, ContractId, Coop, CoopId, Event,
+    EventData, EventIndex, Market, OfferId, Player, Prices, Redaction, State, Time, TradeOffer,
+    Trip, Truck, UpdateError, UserId, TRAVEL_TIME,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// What everyone may know about a truck.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct TruckSummary {
+    pub trip: Option<Trip>,
+    pub wait: Time,
+}
+
+impl TruckSummary {
+    /// Follows [`Truck::tick`].
+    fn tick(&mut self) {
+        self.wait = self.wait.saturating_sub(1);
+    }
+
+    /// Follows [`Truck::arrive`].
+    fn arrive(&mut self) {
+        match self.trip {
+            Some(Trip::Outbound { .. } | Trip::Delivery { .. }) => {
+                self.trip = Some(Trip::Returning);
+                self.wait = TRAVEL_TIME;
+            }
+            Some(Trip::Returning) | None => {
+                self.trip = None;
+            }
+        }
+    }
+}
+
+/// What everyone may know about a player.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerSummary {
+    pub username: String,
+    /// Whether the player is connected.
+    pub online: bool,
+    pub trucks: Vec<TruckSummary>,
+}
+
+/// The state as a single player gets to see it: their own player in full,
+/// the public parts of the world and a summary of everyone else. What other
+/// players do reaches it as outcomes, see [`Redaction`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct View {
+    receiver: UserId,
+    /// Holds no player but the receiver, and only their offers and fills.
+    state: State,
+    others: BTreeMap<UserId, PlayerSummary>,
+}
+
+impl View {
+    pub fn receiver(&self) -> UserId {
+        self.receiver
+    }
+
+    /// `None` until the receiver's player is added.
+    pub fn me(&self) -> Option<&Player> {
+        self.state.players.get(&self.receiver)
+    }
+
+    pub fn others(&self) -> impl Iterator<Item = (UserId, &PlayerSummary)> + '_ {
+        self.others.iter().map(|(user_id, other)| (*user_id, other))
+    }
+
+    pub fn username(&self, user_id: UserId) -> Option<&str> {
+        match self.me() {
+            Some(me) if user_id == self.receiver => Some(&me.username),
+            _ => self.others.get(&user_id).map(|other| &*other.username),
+        }
+    }
+
+    pub fn time(&self) -> Time {
+        self.state.time
+    }
+
+    pub fn climate(&self) -> Climate {
+        self.state.climate()
+    }
+
+    /// The co-op of the receiver, with what its silo holds.
+    pub fn coop(&self) -> Option<&Coop> {
+        self.state.coop_of(self.receiver)
+    }
+
+    /// All co-ops, only the receiver's shows its silo.
+    pub fn coops(&self) -> &BTreeMap<CoopId, Coop> {
+        &self.state.coops
+    }
+
+    pub fn contracts(&self) -> &BTreeMap<ContractId, Contract> {
+        &self.state.contracts
+    }
+
+    /// Offers the receiver made or received.
+    pub fn offers(&self) -> &BTreeMap<OfferId, TradeOffer> {
+        &self.state.offers
+    }
+
+    /// All orders, but only the receiver's fills.
+    pub fn market(&self) -> &Market {
+        &self.state.market
+    }
+
+    pub fn prices(&self) -> &Prices {
+        &self.state.prices
+    }
+
+    pub fn chat(&self) -> &VecDeque<ChatMessage> {
+        &self.state.chat
+    }
+
+    pub fn update(&mut self, catalog: &Catalog, event: EventData) -> Result<(), UpdateError> {
+        let tick = matches!(event.event, Event::Tick);
+        let redactions = match &event.event {
+            Event::Redacted(redactions) => redactions.clone(),
+            _ => Vec::new(),
+        };
+        self.state.update(catalog, event)?;
+
+        if tick {
+            // Without the secret seed the own farm can only be caught up on
+            // the tick it is rolled from, even while the server lets it idle.
+            self.state.catch_up(catalog, self.receiver);
+            for truck in self.others.values_mut().flat_map(|other| &mut other.trucks) {
+                truck.tick();
+            }
+        }
+        for redaction in redactions {
+            self.summarize(redaction);
+        }
+        Ok(())
+    }
+
+    /// Keeps the summaries of others in step, the state has done its part.
+    fn summarize(&mut self, redaction: Redaction) {
+        match redaction {
+            Redaction::Player(user_id, summary) if user_id != self.receiver => {
+                self.others.insert(user_id, summary);
+            }
+            Redaction::Removed(user_id) => {
+                self.others.remove(&user_id);
+            }
+            Redaction::Online(user_id, online) => {
+                if let Some(other) = self.others.get_mut(&user_id) {
+                    other.online = online;
+                }
+            }
+            Redaction::TruckBought(user_id) => {
+                if let Some(other) = self.others.get_mut(&user_id) {
+                    other.trucks.push(TruckSummary::default());
+                }
+            }
+            Redaction::Dispatched {
+                user_id,
+                truck: truck_index,
+                trip,
+            } => {
+                if let Some(truck) = self.truck_mut(user_id, truck_index) {
+                    truck.trip = Some(trip);
+                    truck.wait = TRAVEL_TIME;
+                }
+            }
+            Redaction::Arrived {
+                user_id,
+                truck: truck_index,
+                ..
+            } => {
+                if let Some(truck) = self.truck_mut(user_id, truck_index) {
+                    truck.arrive();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn truck_mut(&mut self, user_id: UserId, truck: usize) -> Option<&mut TruckSummary> {
+        self.others
+            .get_mut(&user_id)
+            .and_then(|other| other.trucks.get_mut(truck))
+    }
+
+    pub fn skip(&mut self, event_idx: EventIndex) -> Result<(), UpdateError> {
+        self.state.skip(event_idx)
+    }
+
+    pub fn take_unlocked(&mut self) -> Vec<(UserId, Achievement)> {
+        self.state.take_unlocked()
+    }
+}
+
+impl Player {
+    pub fn summary(&self) -> PlayerSummary {
+        PlayerSummary {
+            username: self.username.clone(),
+            online: self.connections > 0,
+            trucks: self.farm.trucks.iter().map(Truck::summary).collect(),
+        }
+    }
+}
+
+impl Truck {
+    pub fn summary(&self) -> TruckSummary {
+        TruckSummary {
+            trip: self.trip,
+            wait: self.wait,
+        }
+    }
+}
+
+impl State {
+    /// The receiver's farm is caught up first, as that takes the seeds of
+    /// past ticks.
+    pub fn view(&self, catalog: &Catalog, receiver: UserId) -> View {
+        let mut state = self.clone();
+        state.catch_up(catalog, receiver);
+
+        let others = state
+            .players
+            .iter()
+            .filter(|(user_id, _)| **user_id != receiver)
+            .map(|(user_id, player)| (*user_id, player.summary()))
+            .collect();
+        state.players.retain(|user_id, _| *user_id == receiver);
+        state
+            .offers
+            .retain(|_, offer| offer.from == receiver || offer.to == receiver);
+        state
+            .market
+            .fills
+            .retain(|fill| fill.buyer == receiver || fill.seller == receiver);
+        for coop in state.coops.values_mut() {
+            if !coop.is_member(receiver) {
+                coop.silo.storage.clear();
+            }
+        }
+        state.chat.retain(|message| message.is_visible_to(receiver));
+        // Farms of others roll from these.
+        state.tick_seed = 0;
+        state.secret_seed = 0;
+
+        View {
+            receiver,
+            state,
+            others,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{check, Farm, Money, RejectReason, Time, ONE_MINUTE, SPEED};
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// Pests show up on a field once in this many ticks on average.
+pub const PEST_ODDS: u32 = (30 * ONE_MINUTE * SPEED) as u32;
+/// A healthy field falls ill once in this many ticks on average.
+pub const DISEASE_ODDS: u32 = (45 * ONE_MINUTE * SPEED) as u32;
+/// Ticks between a diseased field losing a veggie and infecting its
+/// neighbours.
+pub const DISEASE_INTERVAL: Time = ONE_MINUTE * SPEED;
+pub const PROTECTION_TIME: Time = 10 * ONE_MINUTE * SPEED;
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq, Ord, PartialOrd,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Countermeasure {
+    /// Keeps pests away.
+    Pesticide,
+    /// Cures diseased fields and keeps them healthy.
+    Fungicide,
+}
+
+impl Countermeasure {
+    pub fn price(self) -> Money {
+        match self {
+            Countermeasure::Pesticide => 15,
+            Countermeasure::Fungicide => 20,
+        }
+    }
+}
+
+impl Farm {
+    pub fn is_protected(&self, countermeasure: Countermeasure, time: Time) -> bool {
+        self.protection
+            .get(&countermeasure)
+            .is_some_and(|until| *until > time)
+    }
+
+    pub fn buy_countermeasure(
+        &mut self,
+        countermeasure: Countermeasure,
+        time: Time,
+    ) -> Result<(), RejectReason> {
+        let price = countermeasure.price();
+        check(self.money >= price, RejectReason::NotEnoughMoney)?;
+        self.money -= price;
+
+        self.protection
+            .insert(countermeasure, time + PROTECTION_TIME);
+        if countermeasure == Countermeasure::Fungicide {
+            for field in &mut self.fields {
+                field.disease = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rolls for new pests and diseases and lets existing diseases progress.
+    pub(crate) fn incidents(&mut self, time: Time, rng: &mut SmallRng) {
+        let pesticide = self.is_protected(Countermeasure::Pesticide, time);
+        let fungicide = self.is_protected(Countermeasure::Fungicide, time);
+
+        let mut spreading = Vec::new();
+        for (i, field) in self.fields.iter_mut().enumerate() {
+            let Some(veggies) = &field.veggies else {
+                continue;
+            };
+
+            // Pests eat half of the crop.
+            if rng.gen_ratio(1, PEST_ODDS) && !pesticide {
+                field.damage(veggies.qty().div_ceil(2));
+            }
+
+            match &mut field.disease {
+                Some(0) => {
+                    field.damage(1);
+                    if field.veggies.is_some() {
+                        field.disease = Some(DISEASE_INTERVAL);
+                    }
+                    spreading.push(i);
+                }
+                Some(wait) => *wait -= 1,
+                None => {
+                    if rng.gen_ratio(1, DISEASE_ODDS) && !fungicide {
+                        field.disease = Some(DISEASE_INTERVAL);
+                    }
+                }
+            }
+        }
+
+        for i in spreading {
+            for neighbour in [i.wrapping_sub(1), i + 1] {
+                if let Some(field) = self.fields.get_mut(neighbour) {
+                    if field.veggies.is_some() && field.disease.is_none() {
+                        field.disease = Some(DISEASE_INTERVAL);
+                    }
+                }
+            }
+        }
+    }
+}
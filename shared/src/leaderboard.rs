@@ -0,0 +1,93 @@
+use crate::{Farm, State, Time, UserId, ONE_DAY, SPEED};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use strum::{Display, EnumIter};
+
+pub const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq, Ord, PartialOrd,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Metric {
+    Money,
+    Harvested,
+    Trades,
+}
+
+impl Metric {
+    pub fn of(self, farm: &Farm) -> u64 {
+        match self {
+            Metric::Money => farm.money,
+            Metric::Harvested => farm.stats.harvested,
+            Metric::Trades => farm.stats.trades,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq)]
+#[strum(serialize_all = "title_case")]
+pub enum Window {
+    Day,
+    Week,
+    AllTime,
+}
+
+impl Window {
+    /// Ticks the window looks back, `None` for all time.
+    pub fn length(self) -> Option<Time> {
+        match self {
+            Window::Day => Some(ONE_DAY * SPEED),
+            Window::Week => Some(7 * ONE_DAY * SPEED),
+            Window::AllTime => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub user_id: UserId,
+    pub username: String,
+    pub value: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Leaderboard {
+    pub metric: Metric,
+    pub window: Window,
+    pub entries: Vec<Entry>,
+}
+
+impl State {
+    /// Ranks the players by how much a metric grew since `baseline`, or by
+    /// its current value without one.
+    pub fn leaderboard(
+        &self,
+        metric: Metric,
+        window: Window,
+        baseline: Option<&BTreeMap<UserId, u64>>,
+    ) -> Leaderboard {
+        let mut entries: Vec<Entry> = self
+            .players
+            .iter()
+            .map(|(user_id, player)| {
+                let before = baseline.and_then(|b| b.get(user_id)).copied().unwrap_or(0);
+                Entry {
+                    user_id: *user_id,
+                    username: player.username.clone(),
+                    value: metric.of(&player.farm).saturating_sub(before),
+                }
+            })
+            .collect();
+
+        // Ties go to the older account.
+        entries.sort_by(|a, b| b.value.cmp(&a.value).then(a.user_id.cmp(&b.user_id)));
+        entries.truncate(LEADERBOARD_SIZE);
+
+        Leaderboard {
+            metric,
+            window,
+            entries,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! The state as the first version of the game saved it, before snapshots
+//! were versioned. Only ever read, to migrate old worlds.
+
+use crate::{Catalog, EventIndex, Money, Quantity, Time, UserId, GROWTH_RATE};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+#[derive(Deserialize, Debug)]
+pub struct State {
+    players: HashMap<UserId, Player>,
+    time: Time,
+    next_event_idx: EventIndex,
+}
+
+#[derive(Deserialize, Debug)]
+struct Player {
+    username: String,
+    last_online: Time,
+    farm: Farm,
+}
+
+#[derive(Deserialize, Debug)]
+struct Farm {
+    fields: Vec<Field>,
+    trucks: Vec<Truck>,
+    silos: Vec<Silo>,
+    tractors: Vec<Tractor>,
+    money: Money,
+}
+
+#[derive(Deserialize, Debug)]
+struct Tractor {
+    wait: Time,
+}
+
+#[derive(Deserialize, Debug)]
+struct Truck {
+    veggies: Option<VeggieQty>,
+    wait: Time,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+enum Veggie {
+    Carrot,
+    Potato,
+}
+
+#[derive(Deserialize, Debug)]
+struct VeggieQty {
+    veggie: Veggie,
+    qty: Quantity,
+    max: Option<Quantity>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Field {
+    veggies: Option<VeggieQty>,
+    max_veggies: Quantity,
+}
+
+#[derive(Deserialize, Debug)]
+struct Silo {
+    storage: VecDeque<VeggieQty>,
+    max_storage: usize,
+}
+
+impl State {
+    /// Brings an old world up to date, everything added since starts out
+    /// empty.
+    pub fn migrate(self, catalog: &Catalog) -> crate::State {
+        let time = self.time;
+        crate::State {
+            players: self
+                .players
+                .into_iter()
+                .map(|(user_id, player)| (user_id, player.migrate(time, catalog)))
+                .collect(),
+            time: self.time,
+            next_event_idx: self.next_event_idx,
+            ..crate::State::new()
+        }
+    }
+}
+
+impl Player {
+    fn migrate(self, time: Time, catalog: &Catalog) -> crate::Player {
+        crate::Player {
+            username: self.username,
+            last_online: self.last_online,
+            synced: time,
+            connections: 0,
+            farm: self.farm.migrate(catalog),
+            achievements: BTreeSet::new(),
+            coop: None,
+        }
+    }
+}
+
+impl Farm {
+    fn migrate(self, catalog: &Catalog) -> crate::Farm {
+        crate::Farm {
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.migrate(catalog))
+                .collect(),
+            trucks: self.trucks.into_iter().map(Truck::migrate).collect(),
+            silos: self.silos.into_iter().map(Silo::migrate).collect(),
+            tractors: self
+                .tractors
+                .into_iter()
+                .map(|tractor| crate::Tractor { wait: tractor.wait })
+                .collect(),
+            money: self.money,
+            protection: Default::default(),
+            stats: Default::default(),
+        }
+    }
+}
+
+impl Truck {
+    fn migrate(self) -> crate::Truck {
+        crate::Truck {
+            veggies: self.veggies.map(VeggieQty::migrate),
+            max_veggies: 1,
+            level: 0,
+            wait: self.wait,
+            trip: None,
+        }
+    }
+}
+
+impl Veggie {
+    /// The catalog took over the ids of the veggies that used to be built
+    /// in.
+    fn migrate(self) -> crate::Veggie {
+        match self {
+            Veggie::Carrot => crate::Veggie(0),
+            Veggie::Potato => crate::Veggie(1),
+        }
+    }
+}
+
+impl VeggieQty {
+    fn migrate(self) -> crate::VeggieQty {
+        let veggies = crate::VeggieQty::new(self.veggie.migrate(), self.qty);
+        match self.max {
+            Some(max) => veggies.with_max(max),
+            None => veggies,
+        }
+    }
+}
+
+impl Field {
+    /// Crops used to be ready right away, so they are ripe.
+    fn migrate(self, catalog: &Catalog) -> crate::Field {
+        let growth_time = self
+            .veggies
+            .as_ref()
+            .and_then(|veggies| catalog.get(veggies.veggie.migrate()))
+            .map_or(0, |spec| spec.growth_ticks());
+
+        crate::Field {
+            veggies: self.veggies.map(VeggieQty::migrate),
+            max_veggies: self.max_veggies,
+            level: 0,
+            age: growth_time * GROWTH_RATE,
+            growth_time,
+            disease: None,
+        }
+    }
+}
+
+impl Silo {
+    fn migrate(self) -> crate::Silo {
+        crate::Silo {
+            storage: self.storage.into_iter().map(VeggieQty::migrate).collect(),
+            max_storage: self.max_storage,
+            level: 0,
+        }
+    }
+}
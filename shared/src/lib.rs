@@ -1,10 +1,45 @@
-use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+use rand::{rngs::SmallRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
     path::PathBuf,
 };
-use strum::{Display, EnumCount, EnumIter, IntoEnumIterator, IntoStaticStr};
+use strum::{Display, EnumIter, IntoStaticStr};
+
+mod achievement;
+mod catalog;
+mod chat;
+mod contract;
+mod coop;
+mod filter;
+mod fog;
+mod incident;
+mod leaderboard;
+pub mod legacy;
+mod market;
+mod offline;
+mod pricing;
+#[cfg(test)]
+mod testing;
+mod trade;
+mod validate;
+mod weather;
+
+pub use achievement::*;
+pub use catalog::*;
+pub use chat::*;
+pub use contract::*;
+pub use coop::*;
+pub use filter::*;
+pub use fog::*;
+pub use incident::*;
+pub use leaderboard::*;
+pub use market::*;
+pub use pricing::*;
+pub use trade::*;
+pub use validate::*;
+pub use weather::*;
 
 #[cfg(not(debug_assertions))]
 pub const SPEED: u64 = 2;
@@ -14,17 +49,14 @@ pub const ONE_MINUTE: u64 = 60;
 pub const ONE_HOUR: u64 = ONE_MINUTE * 60;
 pub const ONE_DAY: u64 = ONE_HOUR * 24;
 
-pub const QTY_LEAST: u64 = 6;
-pub const QTY_GAP: u64 = 2;
-pub const QTY_MOST: u64 = QTY_LEAST + (Veggie::COUNT as u64 - 1) * QTY_GAP;
-pub const QTY_TOTAL: u64 = (QTY_LEAST + QTY_MOST) / 2 * Veggie::COUNT as u64;
+pub const STARTING_MONEY: Money = 10;
+pub const TRAVEL_TIME: Time = 30 * SPEED;
 
 pub type UserId = i64;
 pub type Time = u64;
 pub type Seed = u64;
 pub type Quantity = u64;
 pub type Money = u64;
-pub type Success = bool;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Event {
@@ -32,7 +64,34 @@ pub enum Event {
     AddPlayer(UserId, String),
     EditPlayer(UserId, String),
     RemovePlayer(UserId),
-    Trade(usize, UserId, usize),
+    RestorePlayer(UserId, Box<Player>),
+    Connect(UserId),
+    Disconnect(UserId),
+    /// A truck is done with its trip, sent by the server right after the
+    /// tick that brought it there.
+    Arrive(UserId, usize),
+    ProposeTrade(usize, UserId, usize),
+    AcceptTrade(OfferId),
+    DeclineTrade(OfferId),
+    DeliverContract(usize, ContractId),
+    CreateCoop(String),
+    JoinCoop(CoopId),
+    LeaveCoop,
+    KickMember(UserId),
+    Deposit(usize),
+    PlantFromCoop,
+    SendMessage(Channel, String),
+    /// Only ever sent to players in place of an event they may not see, it
+    /// tells them what came out of it.
+    Redacted(Vec<Redaction>),
+    PlantField(usize),
+    HarvestField(usize),
+    LoadTruck(usize),
+    Buy(Building),
+    Upgrade(Building, usize),
+    BuyCountermeasure(Countermeasure),
+    PlaceOrder(Side, Veggie, Quantity, Money),
+    CancelOrder(OrderId),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -43,163 +102,552 @@ pub struct EventData {
     pub event_idx: EventIndex,
 }
 
-impl EventData {
-    pub fn filter(&self, _receiver: UserId) -> bool {
-        /*
-        let EventData { event, user_id } = self;
-        let user_id = *user_id;
+pub type EventIndex = u64;
 
-        match event {
-            _ => true,
-        }
-        */
+/// Why an event couldn't be applied to a state.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum UpdateError {
+    /// Events were missed in between.
+    OutOfOrder {
+        expected: EventIndex,
+        received: EventIndex,
+    },
+    /// The event was sent by a player that isn't part of the state.
+    UnknownPlayer(UserId),
+    /// The event left the state inconsistent.
+    InvariantViolation(String),
+}
 
-        true
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::OutOfOrder { expected, received } => {
+                write!(f, "expected event {}, received {}", expected, received)
+            }
+            UpdateError::UnknownPlayer(user_id) => write!(f, "unknown player {}", user_id),
+            UpdateError::InvariantViolation(invariant) => {
+                write!(f, "invariant violated, {}", invariant)
+            }
+        }
     }
 }
 
-pub type EventIndex = u64;
+impl std::error::Error for UpdateError {}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Req {
     Event(Event),
+    /// Asks for a fresh sync, after an event couldn't be applied.
+    Resync,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Res {
-    Sync(SyncData),
+    Sync(Box<SyncData>),
     Event(EventData),
+    /// Stands in for an event the receiver may not see.
+    Skip(EventIndex),
+    Achievement(Achievement),
+    Leaderboards(Vec<Leaderboard>),
+    /// Answers an event of the receiver that was turned down.
+    Rejected(RejectReason),
+    /// The server failed to apply an event and went back to its last
+    /// snapshot, a sync follows.
+    Recovered(UpdateError),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SyncData {
     pub user_id: UserId,
-    pub state: State,
+    pub catalog: Catalog,
+    pub state: View,
 }
 
 // MODIFY EVENTS AND STATE BELOW
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct State {
-    pub players: HashMap<UserId, Player>,
+    // Ordered so that every replay iterates the players in the same order.
+    pub players: BTreeMap<UserId, Player>,
     pub time: Time,
     pub next_event_idx: EventIndex,
+    pub refills: RefillSchedule,
+    pub offers: BTreeMap<OfferId, TradeOffer>,
+    pub next_offer_id: OfferId,
+    pub market: Market,
+    pub prices: Prices,
+    /// Drawn on the first tick, the weather follows from it.
+    pub seed: Seed,
+    pub contracts: BTreeMap<ContractId, Contract>,
+    pub next_contract_id: ContractId,
+    pub coops: BTreeMap<CoopId, Coop>,
+    pub next_coop_id: CoopId,
+    pub chat: VecDeque<ChatMessage>,
+    /// The seed of the current tick, farms roll from it.
+    pub tick_seed: Seed,
+    /// The seeds of all ticks follow from it. Only the server knows it, views
+    /// leave it out.
+    pub secret_seed: Seed,
+    #[serde(skip)]
+    unlocked: Vec<(UserId, Achievement)>,
+    /// What came out of the last event, for the players who can't replay it.
+    #[serde(skip)]
+    outcomes: Vec<(Audience, Redaction)>,
 }
 
 impl State {
     pub fn update(
         &mut self,
+        catalog: &Catalog,
         EventData {
             event,
             seed,
             user_id,
             event_idx,
         }: EventData,
-    ) -> Option<()> {
+    ) -> Result<(), UpdateError> {
         if event_idx < self.next_event_idx {
-            return Some(());
+            return Ok(());
         } else if event_idx > self.next_event_idx {
-            return None;
+            return Err(UpdateError::OutOfOrder {
+                expected: self.next_event_idx,
+                received: event_idx,
+            });
         } else {
             self.next_event_idx += 1;
         }
+        self.outcomes.clear();
 
         let mut rng: SmallRng = SmallRng::seed_from_u64(seed);
 
         if let Some(user_id) = user_id {
-            self
-                .players
-                .get_mut(&user_id)?
+            self.catch_up(catalog, user_id);
+            self.players
+                .get_mut(&user_id)
+                .ok_or(UpdateError::UnknownPlayer(user_id))?
                 .last_online = self.time;
         }
-        
 
         match event {
             Event::Tick => {
-                self.time += 1;
+                self.tick(catalog, seed);
             }
             Event::AddPlayer(user_id, username) => {
-                let player = Player::new(username, self.time, &mut rng);
+                let player = Player::new(username, self.time, catalog, &mut rng);
                 self.players.insert(user_id, player);
+                self.tell_player(user_id);
             }
             Event::EditPlayer(user_id, username) => {
-                self.players.get_mut(&user_id)?.username = username;
+                // Archived players take their name from the users table
+                // once they are back.
+                if let Some(player) = self.players.get_mut(&user_id) {
+                    player.username = username;
+                    self.tell_player(user_id);
+                }
             }
             Event::RemovePlayer(user_id) => {
-                self.players.remove(&user_id);
+                self.leave_coop(user_id).ok();
+                self.remove_player(user_id);
+                self.tell(Audience::Everyone, Redaction::Removed(user_id));
+            }
+            Event::RestorePlayer(user_id, player) => {
+                self.restore_player(catalog, user_id, *player);
+            }
+            Event::Connect(user_id) => {
+                self.connect(catalog, user_id);
+            }
+            Event::Disconnect(user_id) => {
+                self.disconnect(user_id);
+            }
+            Event::Arrive(user_id, truck_index) => {
+                self.arrive(catalog, user_id, truck_index);
             }
-            Event::Trade(visitor_truck, visited, visited_truck) => {
-                if let Some(visitor) = user_id {
-                    self.trade(visitor, visited, visitor_truck, visited_truck);
+            Event::Redacted(redactions) => {
+                for redaction in redactions {
+                    self.apply_redaction(catalog, redaction);
+                }
+            }
+            event => {
+                if let Some(user_id) = user_id {
+                    // Rejected events leave the state as it is, every replica
+                    // rejects them alike.
+                    self.apply(catalog, event, user_id, &mut rng).ok();
                 }
             }
         }
 
-        Some(())
+        self.check_achievements();
+
+        self.check_invariants()
     }
 
-    pub fn view(&self, _receiver: UserId) -> Self {
-        State { ..self.clone() }
+    fn restore_player(&mut self, catalog: &Catalog, user_id: UserId, mut player: Player) {
+        // Coming back counts as activity.
+        player.last_online = self.time;
+        // The coop was left on removal.
+        player.coop = None;
+        self.players.entry(user_id).or_insert(player);
+        // Farms are archived as they were when last synced.
+        self.catch_up(catalog, user_id);
+        self.tell_player(user_id);
     }
 
-    pub fn trade(
+    pub(crate) fn remove_player(&mut self, user_id: UserId) {
+        self.offers
+            .retain(|_, offer| offer.from != user_id && offer.to != user_id);
+        self.market
+            .orders
+            .retain(|_, order| order.user_id != user_id);
+        self.players.remove(&user_id);
+    }
+
+    /// Tells everyone what they may know about a player.
+    fn tell_player(&mut self, user_id: UserId) {
+        if let Some(player) = self.players.get(&user_id) {
+            let summary = player.summary();
+            self.tell(Audience::Everyone, Redaction::Player(user_id, summary));
+        }
+    }
+
+    /// Takes what came out of the last event, see [`Filter`].
+    pub fn take_outcomes(&mut self) -> Vec<(Audience, Redaction)> {
+        std::mem::take(&mut self.outcomes)
+    }
+
+    pub(crate) fn tell(&mut self, audience: Audience, redaction: Redaction) {
+        self.outcomes.push((audience, redaction));
+    }
+
+    /// Players and co-ops must agree on who is a member where.
+    fn check_invariants(&self) -> Result<(), UpdateError> {
+        for (user_id, player) in &self.players {
+            if let Some(coop_id) = player.coop {
+                if !self
+                    .coops
+                    .get(&coop_id)
+                    .is_some_and(|c| c.is_member(*user_id))
+                {
+                    return Err(UpdateError::InvariantViolation(format!(
+                        "player {} isn't a member of co-op {}",
+                        user_id, coop_id
+                    )));
+                }
+            }
+        }
+        for (coop_id, coop) in &self.coops {
+            if coop.members.is_empty() || coop.members.len() != coop.seniority.len() {
+                return Err(UpdateError::InvariantViolation(format!(
+                    "co-op {} has inconsistent members",
+                    coop_id
+                )));
+            }
+            // Views hold no player but their receiver.
+            for (user_id, player) in coop
+                .members
+                .keys()
+                .filter_map(|user_id| self.players.get(user_id).map(|p| (user_id, p)))
+            {
+                if player.coop != Some(*coop_id) {
+                    return Err(UpdateError::InvariantViolation(format!(
+                        "member {} of co-op {} isn't in it",
+                        user_id, coop_id
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies an event of a player, or tells why it can't be applied, in
+    /// which case nothing changes.
+    fn apply(
         &mut self,
-        visitor: UserId,
-        visited: UserId,
-        visitor_truck: usize,
-        visited_truck: usize,
-    ) {
-        let visitor_unloaded_veggies = self
-            .players
-            .get_mut(&visitor)
-            .and_then(|p| p.farm.trucks.get_mut(visitor_truck))
-            .and_then(|t| t.veggies.take());
-        let visited_unloaded_veggies = self
-            .players
-            .get_mut(&visited)
-            .and_then(|p| p.farm.trucks.get_mut(visited_truck))
-            .and_then(|t| t.veggies.take());
-
-        let (visitor_veggies_to_load, visited_veggies_to_load) =
-            match (visitor_unloaded_veggies, visited_unloaded_veggies) {
-                (Some(mut visitor_unloaded_veggies), Some(visited_unloaded_veggies)) => {
-                    // Try to plant veggies here.
-                    if let Some(visited_farm) = self.players.get_mut(&visited).map(|p| &mut p.farm)
-                    {
-                        visited_farm.plant_veggies(&mut visitor_unloaded_veggies);
-                    }
-
-                    if visitor_unloaded_veggies.is_empty() {
-                        // Trade successful
-                        (Some(visited_unloaded_veggies), None)
-                    } else {
-                        // Trade unsuccessful
-                        (
-                            Some(visitor_unloaded_veggies),
-                            Some(visited_unloaded_veggies),
-                        )
-                    }
+        catalog: &Catalog,
+        event: Event,
+        user_id: UserId,
+        rng: &mut SmallRng,
+    ) -> Result<(), RejectReason> {
+        match event {
+            Event::Tick
+            | Event::AddPlayer(_, _)
+            | Event::EditPlayer(_, _)
+            | Event::RemovePlayer(_)
+            | Event::RestorePlayer(_, _)
+            | Event::Connect(_)
+            | Event::Disconnect(_)
+            | Event::Arrive(_, _)
+            | Event::Redacted(_) => Err(RejectReason::NotAllowed),
+            Event::ProposeTrade(offered_truck, to, requested_truck) => {
+                self.propose_trade(user_id, offered_truck, to, requested_truck)
+            }
+            Event::AcceptTrade(offer_id) => self.accept_trade(user_id, offer_id),
+            Event::DeclineTrade(offer_id) => self.decline_trade(user_id, offer_id),
+            Event::DeliverContract(truck_index, contract_id) => {
+                self.deliver_contract(user_id, truck_index, contract_id)
+            }
+            Event::CreateCoop(name) => self.create_coop(user_id, name),
+            Event::JoinCoop(coop_id) => self.join_coop(user_id, coop_id),
+            Event::LeaveCoop => self.leave_coop(user_id),
+            Event::KickMember(member) => self.kick_member(user_id, member),
+            Event::Deposit(silo_index) => self.deposit(user_id, silo_index),
+            Event::PlantFromCoop => self.plant_from_coop(user_id, catalog),
+            Event::SendMessage(channel, text) => self.send_message(user_id, channel, text),
+            Event::PlantField(silo_index) => self
+                .farm_mut(user_id)
+                .ok_or(RejectReason::UnknownPlayer)?
+                .plant_field(silo_index, catalog),
+            Event::HarvestField(field_index) => {
+                let harvested = self
+                    .farm(user_id)
+                    .and_then(|farm| farm.fields.get(field_index))
+                    .and_then(|field| field.clone().harvest());
+                self.players
+                    .get_mut(&user_id)
+                    .ok_or(RejectReason::UnknownPlayer)?
+                    .farm
+                    .harvest_field(field_index, catalog, &mut self.prices)?;
+                // The harvest moves the price.
+                if let Some(veggies) = harvested {
+                    self.tell(Audience::Everyone, Redaction::Harvested(veggies));
                 }
-                // Invalid indices.
-                (visitor_unloaded_veggies, visited_unloaded_veggies) => {
-                    (visitor_unloaded_veggies, visited_unloaded_veggies)
+                Ok(())
+            }
+            Event::LoadTruck(silo_index) => self
+                .farm_mut(user_id)
+                .ok_or(RejectReason::UnknownPlayer)?
+                .load_truck(silo_index),
+            Event::Buy(building) => {
+                self.farm_mut(user_id)
+                    .ok_or(RejectReason::UnknownPlayer)?
+                    .buy(building, catalog, rng)?;
+                // Trucks are public.
+                if building == Building::Truck {
+                    self.tell(Audience::Everyone, Redaction::TruckBought(user_id));
                 }
-            };
+                Ok(())
+            }
+            Event::Upgrade(building, index) => self
+                .farm_mut(user_id)
+                .ok_or(RejectReason::UnknownPlayer)?
+                .upgrade(building, index),
+            Event::BuyCountermeasure(countermeasure) => {
+                let time = self.time;
+                self.farm_mut(user_id)
+                    .ok_or(RejectReason::UnknownPlayer)?
+                    .buy_countermeasure(countermeasure, time)
+            }
+            Event::PlaceOrder(side, veggie, qty, price) => {
+                self.place_order(catalog, user_id, side, veggie, qty, price)
+            }
+            Event::CancelOrder(order_id) => self.cancel_order(user_id, order_id),
+        }
+    }
+
+    pub(crate) fn farm(&self, user_id: UserId) -> Option<&Farm> {
+        self.players.get(&user_id).map(|p| &p.farm)
+    }
+
+    pub(crate) fn farm_mut(&mut self, user_id: UserId) -> Option<&mut Farm> {
+        self.players.get_mut(&user_id).map(|p| &mut p.farm)
+    }
 
-        if let Some(visitor_truck) = self
+    /// Moves past an event that was filtered out for this replica.
+    pub fn skip(&mut self, event_idx: EventIndex) -> Result<(), UpdateError> {
+        if event_idx > self.next_event_idx {
+            return Err(UpdateError::OutOfOrder {
+                expected: self.next_event_idx,
+                received: event_idx,
+            });
+        } else if event_idx == self.next_event_idx {
+            self.next_event_idx += 1;
+        }
+
+        Ok(())
+    }
+
+    fn tick(&mut self, catalog: &Catalog, seed: Seed) {
+        self.time += 1;
+        if self.time == 1 {
+            self.seed = seed;
+        }
+        self.tick_seed = seed;
+
+        let mut rng = SmallRng::seed_from_u64(seed);
+        self.update_contracts(catalog, &mut rng);
+
+        let active: Vec<UserId> = self
             .players
-            .get_mut(&visitor)
-            .and_then(|p| p.farm.trucks.get_mut(visitor_truck))
+            .iter()
+            .filter(|(_, player)| !player.is_idle(self.time))
+            .map(|(user_id, _)| *user_id)
+            .collect();
+        for user_id in active {
+            self.catch_up(catalog, user_id);
+        }
+
+        self.expire_offers();
+
+        if self.time.is_multiple_of(PRICE_INTERVAL) {
+            self.prices.update(catalog);
+        }
+    }
+
+    /// Sends a loaded truck off to trade with a truck on another farm.
+    pub fn dispatch(
+        &mut self,
+        visitor: UserId,
+        visitor_truck: usize,
+        visited: UserId,
+        visited_truck: usize,
+    ) -> Result<(), RejectReason> {
+        check(visitor != visited, RejectReason::SelfTrade)?;
+        check(
+            self.players.contains_key(&visited),
+            RejectReason::UnknownPlayer,
+        )?;
+
+        let trip = Trip::Outbound {
+            user_id: visited,
+            truck: visited_truck,
+        };
+        self.farm_mut(visitor)
+            .ok_or(RejectReason::UnknownPlayer)?
+            .trucks
+            .get_mut(visitor_truck)
+            .ok_or(RejectReason::NoSuchBuilding)?
+            .dispatch(trip)?;
+        self.tell(
+            Audience::Everyone,
+            Redaction::Dispatched {
+                user_id: visitor,
+                truck: visitor_truck,
+                trip,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Trucks done with their trip, the server sends an [`Event::Arrive`]
+    /// for each.
+    pub fn arrivals(&self) -> Vec<(UserId, usize)> {
+        self.players
+            .iter()
+            .flat_map(|(user_id, player)| {
+                player
+                    .farm
+                    .trucks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, truck)| truck.wait == 0 && truck.trip.is_some())
+                    .map(move |(i, _)| (*user_id, i))
+            })
+            .collect()
+    }
+
+    /// Trades for a truck that arrived at another farm, then sees it on.
+    fn arrive(&mut self, catalog: &Catalog, user_id: UserId, truck_index: usize) {
+        let Some(trip) = self
+            .farm(user_id)
+            .and_then(|farm| farm.trucks.get(truck_index))
+            .filter(|truck| truck.wait == 0)
+            .and_then(|truck| truck.trip)
+        else {
+            return;
+        };
+
+        if let Trip::Outbound {
+            user_id: visited,
+            truck: visited_truck,
+        } = trip
         {
-            visitor_truck.veggies = visitor_veggies_to_load;
+            self.catch_up(catalog, visited);
+
+            // The visited truck must be at home to trade.
+            let at_home = self
+                .farm(visited)
+                .and_then(|farm| farm.trucks.get(visited_truck))
+                .is_some_and(Truck::is_ready);
+
+            if at_home {
+                self.trade(catalog, user_id, visited, truck_index, visited_truck);
+            }
         }
-        if let Some(visited_truck) = self
-            .players
-            .get_mut(&visited)
-            .and_then(|p| p.farm.trucks.get_mut(visited_truck))
+
+        self.finish_trip(catalog, user_id, truck_index, trip);
+        self.tell(
+            Audience::Everyone,
+            Redaction::Arrived {
+                user_id,
+                truck: truck_index,
+                trip,
+            },
+        );
+    }
+
+    /// Delivers for a truck that arrived in town and turns it around, or
+    /// parks it once back home. Everyone can tell how that goes.
+    pub(crate) fn finish_trip(
+        &mut self,
+        catalog: &Catalog,
+        user_id: UserId,
+        truck_index: usize,
+        trip: Trip,
+    ) {
+        if let Trip::Delivery { contract } = trip {
+            self.complete_contract(catalog, user_id, truck_index, contract);
+        }
+        if let Some(truck) = self
+            .farm_mut(user_id)
+            .and_then(|farm| farm.trucks.get_mut(truck_index))
         {
-            visited_truck.veggies = visited_veggies_to_load;
+            truck.arrive();
+        }
+    }
+
+    /// Hands the cargo of the visitor to the visited farm. Each of them is
+    /// told only what ends up on their own farm.
+    fn trade(
+        &mut self,
+        catalog: &Catalog,
+        visitor: UserId,
+        visited: UserId,
+        visitor_truck: usize,
+        visited_truck: usize,
+    ) {
+        let Some(cargo) = self
+            .farm_mut(visitor)
+            .and_then(|farm| farm.trucks.get_mut(visitor_truck))
+            .and_then(|truck| truck.veggies.take())
+        else {
+            return;
+        };
+
+        self.tell(
+            Audience::only(visited),
+            Redaction::Visited {
+                user_id: visited,
+                truck: visited_truck,
+                cargo: cargo.clone(),
+            },
+        );
+        let (cargo, traded) = match self.farm_mut(visited) {
+            Some(farm) => farm.host_trade(visited_truck, cargo, catalog),
+            None => (Some(cargo), false),
+        };
+        if let Some(farm) = self.farm_mut(visitor) {
+            farm.return_from_trade(visitor_truck, cargo.clone(), traded);
         }
+        self.tell(
+            Audience::only(visitor),
+            Redaction::Traded {
+                user_id: visitor,
+                truck: visitor_truck,
+                cargo,
+                traded,
+            },
+        );
     }
 }
 
@@ -207,24 +655,35 @@ impl State {
 pub struct Player {
     pub username: String,
     last_online: Time,
+    /// The time the farm was last ticked to.
+    synced: Time,
+    /// Open connections, a connected player is always online.
+    pub connections: u32,
     pub farm: Farm,
+    pub achievements: BTreeSet<Achievement>,
+    pub coop: Option<CoopId>,
 }
 
 impl Player {
-    pub fn new(username: String, time: Time, rng: &mut SmallRng) -> Self {
+    pub fn new(username: String, time: Time, catalog: &Catalog, rng: &mut SmallRng) -> Self {
         Player {
             username,
             last_online: time,
-            farm: Farm::new(rng),
+            synced: time,
+            connections: 0,
+            farm: Farm::new(catalog, rng),
+            achievements: BTreeSet::new(),
+            coop: None,
         }
     }
 
     pub fn is_online(&self, time: Time) -> bool {
-        (time - self.last_online) / SPEED < ONE_MINUTE * 3
+        self.connections > 0 || (time - self.last_online) / SPEED < ONE_MINUTE * 3
     }
 
-    pub fn is_active(&self, time: Time) -> bool {
-        (time - self.last_online) / SPEED < ONE_DAY
+    /// Whether the player was around within the last `timeout` seconds.
+    pub fn is_active(&self, time: Time, timeout: Time) -> bool {
+        (time - self.last_online) / SPEED < timeout
     }
 }
 
@@ -235,64 +694,371 @@ pub struct Farm {
     pub silos: Vec<Silo>,
     pub tractors: Vec<Tractor>,
     pub money: Money,
+    /// Countermeasures in effect and the time they wear off.
+    pub protection: BTreeMap<Countermeasure, Time>,
+    pub stats: Stats,
 }
 
 impl Farm {
-    pub fn new(rng: &mut SmallRng) -> Self {
+    pub fn new(catalog: &Catalog, rng: &mut SmallRng) -> Self {
         Farm {
-            money: 0,
+            money: STARTING_MONEY,
             fields: Vec::new(),
-            silos: vec![Silo::new(rng)],
+            silos: vec![Silo::new(catalog, rng)],
             trucks: Vec::new(),
             tractors: vec![Tractor::new()],
+            protection: BTreeMap::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn tick(
+        &mut self,
+        time: Time,
+        catalog: &Catalog,
+        climate: &Climate,
+        refills: &RefillSchedule,
+        rng: &mut SmallRng,
+    ) {
+        for field in &mut self.fields {
+            let rate = field
+                .veggies
+                .as_ref()
+                .and_then(|veggies| catalog.get(veggies.veggie()))
+                .map_or(0, |spec| climate.growth_rate(spec, time));
+            field.tick(rate);
+        }
+        self.incidents(time, rng);
+        for silo in &mut self.silos {
+            silo.tick(time, catalog, refills, rng);
+        }
+        for tractor in &mut self.tractors {
+            tractor.tick();
+        }
+        for truck in &mut self.trucks {
+            truck.tick();
+        }
+    }
+
+    /// Frost costs every young crop out of season a veggie, drought every
+    /// seedling.
+    pub fn weather(&mut self, weather: Weather, time: Time, catalog: &Catalog) {
+        for field in &mut self.fields {
+            let damaged = match (weather, field.growth(), &field.veggies) {
+                (Weather::Frost, Some(Growth::Seedling | Growth::Growing), Some(veggies)) => {
+                    !catalog
+                        .get(veggies.veggie())
+                        .is_some_and(|spec| spec.prefers(Season::at(time)))
+                }
+                (Weather::Drought, Some(Growth::Seedling), _) => true,
+                _ => false,
+            };
+
+            if damaged {
+                field.damage(1);
+            }
+        }
+    }
+
+    pub fn count(&self, building: Building) -> usize {
+        match building {
+            Building::Field => self.fields.len(),
+            Building::Silo => self.silos.len(),
+            Building::Truck => self.trucks.len(),
+            Building::Tractor => self.tractors.len(),
+        }
+    }
+
+    pub fn price(&self, building: Building) -> Money {
+        building.price(self.count(building))
+    }
+
+    pub fn buy(
+        &mut self,
+        building: Building,
+        catalog: &Catalog,
+        rng: &mut SmallRng,
+    ) -> Result<(), RejectReason> {
+        let price = self.price(building);
+        check(self.money >= price, RejectReason::NotEnoughMoney)?;
+        self.money -= price;
+
+        match building {
+            Building::Field => self.fields.push(Field::new()),
+            Building::Silo => self.silos.push(Silo::new(catalog, rng)),
+            Building::Truck => self.trucks.push(Truck::new()),
+            Building::Tractor => self.tractors.push(Tractor::new()),
+        }
+
+        Ok(())
+    }
+
+    pub fn level(&self, building: Building, index: usize) -> Option<u32> {
+        match building {
+            Building::Field => self.fields.get(index).map(|f| f.level),
+            Building::Silo => self.silos.get(index).map(|s| s.level),
+            Building::Truck => self.trucks.get(index).map(|t| t.level),
+            Building::Tractor => None,
+        }
+    }
+
+    pub fn upgrade_price(&self, building: Building, index: usize) -> Option<Money> {
+        building.upgrade_price(self.level(building, index)?)
+    }
+
+    pub fn upgrade(&mut self, building: Building, index: usize) -> Result<(), RejectReason> {
+        check(building != Building::Tractor, RejectReason::NotUpgradable)?;
+        let price = self
+            .upgrade_price(building, index)
+            .ok_or(RejectReason::NoSuchBuilding)?;
+        check(self.money >= price, RejectReason::NotEnoughMoney)?;
+        self.money -= price;
+
+        match building {
+            Building::Field => self.fields[index].upgrade(),
+            Building::Silo => self.silos[index].upgrade(),
+            Building::Truck => self.trucks[index].upgrade(),
+            Building::Tractor => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    pub fn count_veggies(&self, veggie: Veggie) -> Quantity {
+        self.silos
+            .iter()
+            .flat_map(|s| &s.storage)
+            .filter(|v| v.veggie() == veggie)
+            .map(VeggieQty::qty)
+            .sum()
+    }
+
+    /// Removes veggies from the silos, taking the most recently stored first.
+    pub fn take_veggies(&mut self, veggie: Veggie, mut qty: Quantity) {
+        for silo in &mut self.silos {
+            for stored in silo.storage.iter_mut().rev() {
+                if stored.veggie() == veggie {
+                    let taken = stored.take(qty);
+                    qty -= taken.qty();
+                }
+            }
+            silo.storage.retain(|v| !v.is_empty());
         }
     }
 
-    pub fn tick(&mut self) {}
+    pub fn free_storage(&self) -> Quantity {
+        self.silos
+            .iter()
+            .map(|s| s.max_storage.saturating_sub(s.storage.len()) as Quantity)
+            .sum()
+    }
 
-    pub fn plant_field(&mut self, silo_index: usize) {
-        if let Some(mut veggies) = self
+    /// Stores veggies in the silos one by one, as far as there is room.
+    pub fn store_veggies(&mut self, veggie: Veggie, mut qty: Quantity) {
+        for silo in &mut self.silos {
+            while qty > 0 && silo.storage.len() < silo.max_storage {
+                silo.storage
+                    .push_back(VeggieQty::new(veggie, 1).with_max(1));
+                qty -= 1;
+            }
+        }
+    }
+
+    pub fn plant_field(
+        &mut self,
+        silo_index: usize,
+        catalog: &Catalog,
+    ) -> Result<(), RejectReason> {
+        let silo = self
             .silos
             .get_mut(silo_index)
-            .and_then(|s| s.storage.pop_front())
-        {
-            self.plant_veggies(&mut veggies);
+            .ok_or(RejectReason::NoSuchBuilding)?;
 
-            if !veggies.is_empty() {
-                if let Some(silo) = self.silos.get_mut(silo_index) {
-                    silo.storage.push_front(veggies);
+        let mut storage = std::mem::take(&mut silo.storage);
+        let planted = self.plant_from(&mut storage, catalog);
+        self.silos[silo_index].storage = storage;
+
+        planted
+    }
+
+    /// Plants the first veggies of a silo's storage, which may also be a
+    /// co-op's.
+    pub fn plant_from(
+        &mut self,
+        storage: &mut VecDeque<VeggieQty>,
+        catalog: &Catalog,
+    ) -> Result<(), RejectReason> {
+        check(!storage.is_empty(), RejectReason::SiloEmpty)?;
+        let tractor = self
+            .tractors
+            .iter()
+            .position(Tractor::is_ready)
+            .ok_or(RejectReason::TractorsBusy)?;
+
+        let Some(mut veggies) = storage.pop_front() else {
+            return Err(RejectReason::SiloEmpty);
+        };
+        let qty = veggies.qty();
+        self.plant_veggies(&mut veggies, catalog);
+        let planted = veggies.qty() < qty;
+
+        if !veggies.is_empty() {
+            storage.push_front(veggies);
+        }
+        check(planted, RejectReason::NoFreeField)?;
+
+        self.tractors[tractor].work(Job::Plant);
+        Ok(())
+    }
+
+    /// Veggies missing from the catalog can't be planted.
+    pub fn plant_veggies(&mut self, veggies: &mut VeggieQty, catalog: &Catalog) {
+        if let Some(spec) = catalog.get(veggies.veggie()) {
+            for field in &mut self.fields {
+                // Empty fields would take an empty crop.
+                if veggies.is_empty() {
+                    break;
                 }
+                field.plant(veggies, spec.growth_ticks());
             }
         }
     }
 
-    pub fn plant_veggies(&mut self, veggies: &mut VeggieQty) {
-        for field in &mut self.fields {
-            field.plant(veggies);
+    /// Plants the cargo of a truck visiting one of ours. Only if all of it
+    /// was planted does our truck hand over its own cargo in return. Returns
+    /// what the visitor leaves with and whether they traded.
+    pub(crate) fn host_trade(
+        &mut self,
+        truck_index: usize,
+        mut cargo: VeggieQty,
+        catalog: &Catalog,
+    ) -> (Option<VeggieQty>, bool) {
+        let Some(own) = self
+            .trucks
+            .get_mut(truck_index)
+            .and_then(|truck| truck.veggies.take())
+        else {
+            return (Some(cargo), false);
+        };
+
+        self.plant_veggies(&mut cargo, catalog);
+        if cargo.is_empty() {
+            self.stats.trades += 1;
+            (Some(own), true)
+        } else {
+            self.trucks[truck_index].veggies = Some(own);
+            (Some(cargo), false)
         }
     }
 
-    pub fn harvest_field(&mut self, field_index: usize) {
-        if let Some(field) = self.fields.get_mut(field_index) {
-            self.money += field.harvest()
+    /// Loads what a truck takes home from a trade.
+    pub(crate) fn return_from_trade(
+        &mut self,
+        truck_index: usize,
+        cargo: Option<VeggieQty>,
+        traded: bool,
+    ) {
+        if let Some(truck) = self.trucks.get_mut(truck_index) {
+            truck.veggies = cargo;
+        }
+        if traded {
+            self.stats.trades += 1;
         }
     }
 
-    pub fn load_truck(&mut self, silo_index: usize) {
-        if let Some(mut veggies) = self
+    pub fn harvest_field(
+        &mut self,
+        field_index: usize,
+        catalog: &Catalog,
+        prices: &mut Prices,
+    ) -> Result<(), RejectReason> {
+        let field = self
+            .fields
+            .get_mut(field_index)
+            .ok_or(RejectReason::NoSuchBuilding)?;
+        check(
+            matches!(field.growth(), Some(Growth::Ripe | Growth::Withered)),
+            RejectReason::NotRipe,
+        )?;
+        let tractor = self
+            .tractors
+            .iter_mut()
+            .find(|t| t.is_ready())
+            .ok_or(RejectReason::TractorsBusy)?;
+
+        tractor.work(Job::Harvest);
+        if let Some(veggies) = field.harvest() {
+            self.money = self
+                .money
+                .saturating_add(prices.harvest(catalog, &veggies));
+            self.stats.harvests += 1;
+            self.stats.harvested += veggies.qty();
+        }
+
+        Ok(())
+    }
+
+    pub fn load_truck(&mut self, silo_index: usize) -> Result<(), RejectReason> {
+        let silo = self
             .silos
             .get_mut(silo_index)
-            .and_then(|s| s.storage.pop_front())
-        {
-            for truck in &mut self.trucks {
-                truck.load(&mut veggies);
-            }
+            .ok_or(RejectReason::NoSuchBuilding)?;
+        check(!silo.storage.is_empty(), RejectReason::SiloEmpty)?;
+        check(
+            self.trucks.iter().any(Truck::is_ready),
+            RejectReason::TrucksBusy,
+        )?;
+
+        let Some(mut veggies) = silo.storage.pop_front() else {
+            return Err(RejectReason::SiloEmpty);
+        };
+        let qty = veggies.qty();
+        for truck in &mut self.trucks {
+            truck.load(&mut veggies);
+        }
+        let loaded = veggies.qty() < qty;
 
-            if !veggies.is_empty() {
-                if let Some(silo) = self.silos.get_mut(silo_index) {
-                    silo.storage.push_front(veggies);
-                }
-            }
+        if !veggies.is_empty() {
+            silo.storage.push_front(veggies);
+        }
+
+        check(loaded, RejectReason::TrucksFull)
+    }
+}
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq, IntoStaticStr,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Building {
+    Field,
+    Silo,
+    Truck,
+    Tractor,
+}
+
+impl Building {
+    pub fn base_price(self) -> Money {
+        match self {
+            Building::Field => 10,
+            Building::Silo => 20,
+            Building::Truck => 30,
+            Building::Tractor => 30,
+        }
+    }
+
+    /// Every building of the same kind costs twice as much as the previous one.
+    pub fn price(self, owned: usize) -> Money {
+        self.base_price()
+            .saturating_mul(2u64.saturating_pow(owned as u32))
+    }
+
+    /// Upgrading from `level` costs as much as a building of the next level
+    /// would. Tractors have no capacity and can't be upgraded.
+    pub fn upgrade_price(self, level: u32) -> Option<Money> {
+        match self {
+            Building::Tractor => None,
+            _ => Some(self.price(level as usize + 1)),
         }
     }
 }
@@ -315,27 +1081,94 @@ impl Tractor {
     pub fn is_ready(&self) -> bool {
         self.wait == 0
     }
+
+    pub fn work(&mut self, job: Job) {
+        self.wait = job.duration();
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "title_case")]
+pub enum Job {
+    Plant,
+    Harvest,
+}
+
+impl Job {
+    /// Ticks a tractor is busy with the job.
+    pub fn duration(self) -> Time {
+        match self {
+            Job::Plant => 10 * SPEED,
+            Job::Harvest => 15 * SPEED,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Truck {
     veggies: Option<VeggieQty>,
+    pub max_veggies: Quantity,
+    pub level: u32,
     pub wait: Time,
+    pub trip: Option<Trip>,
 }
 
 impl Truck {
     pub fn new() -> Self {
         Truck {
             veggies: None,
+            max_veggies: 1,
+            level: 0,
             wait: 0,
+            trip: None,
         }
     }
 
+    pub fn veggies(&self) -> Option<&VeggieQty> {
+        self.veggies.as_ref()
+    }
+
     pub fn load(&mut self, to_plant: &mut VeggieQty) {
+        if !self.is_ready() {
+            return;
+        }
+
         if let Some(curr) = &mut self.veggies {
             curr.add(to_plant);
         } else {
-            self.veggies = Some(to_plant.take().with_max(1));
+            self.veggies = Some(to_plant.take(self.max_veggies));
+        }
+    }
+
+    pub fn upgrade(&mut self) {
+        self.level += 1;
+        self.max_veggies += 1;
+    }
+
+    pub fn dispatch(&mut self, trip: Trip) -> Result<(), RejectReason> {
+        check(self.is_ready(), RejectReason::TrucksBusy)?;
+        check(self.veggies.is_some(), RejectReason::TruckEmpty)?;
+
+        self.set_off(trip);
+        Ok(())
+    }
+
+    /// Starts a trip, whatever the truck carries.
+    fn set_off(&mut self, trip: Trip) {
+        self.trip = Some(trip);
+        self.wait = TRAVEL_TIME;
+    }
+
+    /// Turns around at the destination, or parks when back home.
+    pub fn arrive(&mut self) {
+        match self.trip {
+            Some(Trip::Outbound { .. } | Trip::Delivery { .. }) => {
+                self.trip = Some(Trip::Returning);
+                self.wait = TRAVEL_TIME;
+            }
+            Some(Trip::Returning) | None => {
+                self.trip = None;
+            }
         }
     }
 
@@ -346,36 +1179,49 @@ impl Truck {
     }
 
     pub fn is_ready(&self) -> bool {
-        self.wait == 0
+        self.wait == 0 && self.trip.is_none()
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Trip {
+    /// On the way to trade with a truck of another farm.
+    Outbound { user_id: UserId, truck: usize },
+    /// On the way to deliver a contract in town.
+    Delivery { contract: ContractId },
+    /// On the way back home after trading.
+    Returning,
+}
+
+/// Identifies a veggie in the [`Catalog`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(transparent)]
+pub struct Veggie(pub u16);
+
 #[derive(
-    Serialize,
-    Deserialize,
-    Clone,
-    Copy,
-    Debug,
-    Display,
-    EnumCount,
-    EnumIter,
-    Eq,
-    PartialEq,
-    IntoStaticStr,
+    Serialize, Deserialize, Clone, Copy, Debug, Display, Eq, PartialEq, IntoStaticStr,
 )]
-#[strum(serialize_all = "title_case")]
-pub enum Veggie {
-    Carrot,
-    Potato,
+#[strum(serialize_all = "kebab-case")]
+pub enum Growth {
+    Seedling,
+    Growing,
+    Ripe,
+    Withered,
 }
 
-impl Veggie {
-    pub fn qty(self) -> Quantity {
-        QTY_LEAST + (self as u64) * QTY_GAP
-    }
-
-    pub fn value(self, qty: Quantity) -> Money {
-        (QTY_TOTAL / self.qty()).pow(qty as u32)
+impl Growth {
+    /// The stage of a crop `age` ticks after planting that takes
+    /// `growth_time` ticks to ripen.
+    pub fn of(age: Time, growth_time: Time) -> Self {
+        if age < growth_time / 2 {
+            Growth::Seedling
+        } else if age < growth_time {
+            Growth::Growing
+        } else if age < growth_time.saturating_mul(2) {
+            Growth::Ripe
+        } else {
+            Growth::Withered
+        }
     }
 }
 
@@ -419,16 +1265,12 @@ impl VeggieQty {
         }
     }
 
-    pub fn take(&mut self) -> Self {
-        let mut new = VeggieQty::new(self.veggie, self.qty);
+    pub fn take(&mut self, max: Quantity) -> Self {
+        let mut new = VeggieQty::new(self.veggie, 0).with_max(max);
         new.add(self);
         new
     }
 
-    pub fn value(&self) -> Money {
-        self.veggie.value(self.qty)
-    }
-
     pub fn is_empty(&self) -> bool {
         self.qty == 0
     }
@@ -438,6 +1280,14 @@ impl VeggieQty {
 pub struct Field {
     pub veggies: Option<VeggieQty>,
     pub max_veggies: Quantity,
+    pub level: u32,
+    /// Growth points since the current crop was planted, see [`GROWTH_RATE`].
+    pub age: Time,
+    /// Ticks the current crop takes to ripen.
+    pub growth_time: Time,
+    /// Ticks until a diseased crop loses a veggie and spreads, `None` if
+    /// healthy.
+    pub disease: Option<Time>,
 }
 
 impl Field {
@@ -445,22 +1295,73 @@ impl Field {
         Field {
             veggies: None,
             max_veggies: 3,
+            level: 0,
+            age: 0,
+            growth_time: 0,
+            disease: None,
         }
     }
 
-    pub fn plant(&mut self, to_plant: &mut VeggieQty) {
-        if let Some(curr) = &mut self.veggies {
-            curr.add(to_plant);
-        } else {
-            self.veggies = Some(to_plant.take().with_max(self.max_veggies));
+    pub fn upgrade(&mut self) {
+        self.level += 1;
+        self.max_veggies += 1;
+    }
+
+    pub fn growth(&self) -> Option<Growth> {
+        self.veggies
+            .as_ref()
+            .map(|_| Growth::of(self.age, self.growth_time * GROWTH_RATE))
+    }
+
+    pub fn plant(&mut self, to_plant: &mut VeggieQty, growth_time: Time) {
+        let growth = self.growth();
+        match (&mut self.veggies, growth) {
+            // Seedlings of the same kind may still be added.
+            (Some(curr), Some(Growth::Seedling)) => {
+                curr.add(to_plant);
+            }
+            (Some(_), _) => {}
+            (None, _) => {
+                self.veggies = Some(to_plant.take(self.max_veggies));
+                self.age = 0;
+                self.growth_time = growth_time;
+            }
         }
     }
 
-    pub fn harvest(&mut self) -> Money {
-        if let Some(veggies) = self.veggies.take() {
-            veggies.value()
-        } else {
-            0
+    /// Clears ripe or withered crops, only ripe ones are returned.
+    pub fn harvest(&mut self) -> Option<VeggieQty> {
+        if matches!(self.growth(), Some(Growth::Ripe | Growth::Withered)) {
+            self.disease = None;
+        }
+        match self.growth() {
+            Some(Growth::Ripe) => self.veggies.take(),
+            Some(Growth::Withered) => {
+                self.veggies = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Destroys up to `qty` veggies, clearing the field if none are left.
+    pub fn damage(&mut self, qty: Quantity) {
+        if let Some(veggies) = &mut self.veggies {
+            veggies.take(qty);
+            if veggies.is_empty() {
+                self.veggies = None;
+                self.disease = None;
+            }
+        }
+    }
+
+    pub fn tick(&mut self, rate: Time) {
+        // Stop counting once withered, nothing changes afterwards.
+        if matches!(
+            self.growth(),
+            Some(Growth::Seedling | Growth::Growing | Growth::Ripe)
+        ) {
+            self.age += rate;
         }
     }
 }
@@ -469,26 +1370,69 @@ impl Field {
 pub struct Silo {
     pub storage: VecDeque<VeggieQty>,
     pub max_storage: usize,
+    pub level: u32,
 }
 
 impl Silo {
-    pub fn new(rng: &mut SmallRng) -> Self {
+    pub fn new(catalog: &Catalog, rng: &mut SmallRng) -> Self {
         let mut barn = Silo {
             storage: VecDeque::new(),
             max_storage: 3,
+            level: 0,
         };
 
-        barn.refill(rng);
+        for _ in 0..barn.max_storage {
+            barn.refill(catalog, rng);
+        }
 
         barn
     }
 
-    pub fn refill(&mut self, rng: &mut SmallRng) {
-        while self.storage.len() < self.max_storage {
-            let veggies: Vec<_> = Veggie::iter().collect();
-            let veggie = veggies.choose_weighted(rng, |v| v.qty()).unwrap();
-            self.storage
-                .push_back(VeggieQty::new(*veggie, 1).with_max(1))
+    pub fn upgrade(&mut self) {
+        self.level += 1;
+        self.max_storage += 1;
+    }
+
+    pub fn tick(
+        &mut self,
+        time: Time,
+        catalog: &Catalog,
+        refills: &RefillSchedule,
+        rng: &mut SmallRng,
+    ) {
+        if refills.is_due(time) {
+            self.refill(catalog, rng);
+        }
+    }
+
+    /// Adds a single veggie unless the silo is full, picked according to
+    /// the catalog weights.
+    pub fn refill(&mut self, catalog: &Catalog, rng: &mut SmallRng) {
+        if self.storage.len() < self.max_storage {
+            if let Some(veggie) = catalog.choose(rng) {
+                self.storage
+                    .push_back(VeggieQty::new(veggie, 1).with_max(1))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RefillSchedule {
+    /// Ticks between two refills of every silo that isn't full.
+    pub interval: Time,
+}
+
+impl RefillSchedule {
+    pub fn is_due(&self, time: Time) -> bool {
+        self.interval > 0 && time.is_multiple_of(self.interval)
+    }
+}
+
+impl Default for RefillSchedule {
+    fn default() -> Self {
+        RefillSchedule {
+            interval: ONE_MINUTE / 3 * SPEED,
         }
     }
 }
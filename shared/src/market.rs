@@ -0,0 +1,326 @@
+use crate::{
+    check, Audience, Catalog, Farm, Money, Quantity, Redaction, RejectReason, State, Time, UserId,
+    Veggie,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+pub type OrderId = u64;
+
+pub const MAX_ORDERS: usize = 20;
+pub const MAX_FILLS: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// A standing offer to buy or sell veggies for a price per veggie.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Order {
+    pub user_id: UserId,
+    pub side: Side,
+    pub veggie: Veggie,
+    pub qty: Quantity,
+    pub price: Money,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Fill {
+    pub buyer: UserId,
+    pub seller: UserId,
+    pub veggie: Veggie,
+    pub qty: Quantity,
+    pub price: Money,
+    pub time: Time,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Market {
+    pub orders: BTreeMap<OrderId, Order>,
+    pub next_order_id: OrderId,
+    /// The most recent fills, oldest first.
+    pub fills: VecDeque<Fill>,
+}
+
+impl Market {
+    pub fn orders_of(&self, user_id: UserId) -> impl Iterator<Item = (&OrderId, &Order)> {
+        self.orders
+            .iter()
+            .filter(move |(_, order)| order.user_id == user_id)
+    }
+}
+
+impl State {
+    /// Places an order and matches it against the book right away.
+    ///
+    /// Nothing is held back while an order rests in the book. Instead, the
+    /// seller's veggies, the buyer's money and room in the buyer's silos are
+    /// checked when a match is found, and orders that can no longer be
+    /// honored are dropped.
+    pub fn place_order(
+        &mut self,
+        catalog: &Catalog,
+        user_id: UserId,
+        side: Side,
+        veggie: Veggie,
+        qty: Quantity,
+        price: Money,
+    ) -> Result<(), RejectReason> {
+        check(
+            qty > 0 && price > 0 && catalog.contains(veggie),
+            RejectReason::InvalidOrder,
+        )?;
+        check(
+            self.market.orders_of(user_id).count() < MAX_ORDERS,
+            RejectReason::TooManyOrders,
+        )?;
+
+        let farm = self.farm(user_id).ok_or(RejectReason::UnknownPlayer)?;
+        match side {
+            Side::Sell => check(
+                farm.count_veggies(veggie) >= qty,
+                RejectReason::NotEnoughVeggies,
+            )?,
+            Side::Buy => check(
+                qty.checked_mul(price)
+                    .is_some_and(|total| total <= farm.money),
+                RejectReason::NotEnoughMoney,
+            )?,
+        }
+
+        let order_id = self.market.next_order_id;
+        self.market.next_order_id += 1;
+        let order = Order {
+            user_id,
+            side,
+            veggie,
+            qty,
+            price,
+        };
+        self.market.orders.insert(order_id, order.clone());
+        self.tell(Audience::Everyone, Redaction::Order(order_id, Some(order)));
+
+        self.match_orders(catalog, veggie);
+
+        Ok(())
+    }
+
+    pub fn cancel_order(&mut self, user_id: UserId, order_id: OrderId) -> Result<(), RejectReason> {
+        check(
+            self.market
+                .orders
+                .get(&order_id)
+                .is_some_and(|order| order.user_id == user_id),
+            RejectReason::NoSuchOrder,
+        )?;
+        self.market.orders.remove(&order_id);
+        self.tell(Audience::Everyone, Redaction::Order(order_id, None));
+
+        Ok(())
+    }
+
+    /// Fills the cheapest sell orders with the highest buy orders, the older
+    /// of both orders setting the price.
+    fn match_orders(&mut self, catalog: &Catalog, veggie: Veggie) {
+        while let Some((sell_id, buy_id)) = self.next_match(veggie) {
+            // Idle farms may be behind on their silo refills.
+            for order_id in [sell_id, buy_id] {
+                let user_id = self.market.orders[&order_id].user_id;
+                self.catch_up(catalog, user_id);
+            }
+
+            let sell = &self.market.orders[&sell_id];
+            let buy = &self.market.orders[&buy_id];
+            let (seller, buyer) = (sell.user_id, buy.user_id);
+            let price = if sell_id < buy_id { sell.price } else { buy.price };
+
+            let available = self.farm(seller).map_or(0, |f| f.count_veggies(veggie));
+            let affordable = self.farm(buyer).map_or(0, |f| f.money / price);
+            let room = self.farm(buyer).map_or(0, Farm::free_storage);
+
+            if available == 0 {
+                self.market.orders.remove(&sell_id);
+                self.tell(Audience::Everyone, Redaction::Order(sell_id, None));
+                continue;
+            }
+            if affordable == 0 || room == 0 {
+                self.market.orders.remove(&buy_id);
+                self.tell(Audience::Everyone, Redaction::Order(buy_id, None));
+                continue;
+            }
+
+            let qty = sell.qty.min(buy.qty).min(available).min(affordable).min(room);
+
+            let fill = Fill {
+                buyer,
+                seller,
+                veggie,
+                qty,
+                price,
+                time: self.time,
+            };
+            // Only the parties learn who traded with whom.
+            let parties = BTreeSet::from([seller, buyer]);
+            self.tell(
+                Audience::Only(parties.clone()),
+                Redaction::Filled(fill.clone()),
+            );
+            self.tell(Audience::AllBut(parties), Redaction::Sold(veggie, qty));
+            self.settle(catalog, fill);
+
+            for order_id in [sell_id, buy_id] {
+                if let Some(order) = self.market.orders.get_mut(&order_id) {
+                    order.qty -= qty;
+                    if order.qty == 0 {
+                        self.market.orders.remove(&order_id);
+                    }
+                }
+                let order = self.market.orders.get(&order_id).cloned();
+                self.tell(Audience::Everyone, Redaction::Order(order_id, order));
+            }
+        }
+    }
+
+    /// Moves the veggies and the money of a fill between its parties.
+    pub(crate) fn settle(&mut self, catalog: &Catalog, fill: Fill) {
+        let total = fill.qty * fill.price;
+        if let Some(farm) = self.farm_mut(fill.seller) {
+            farm.take_veggies(fill.veggie, fill.qty);
+            farm.money = farm.money.saturating_add(total);
+            farm.stats.trades += 1;
+        }
+        if let Some(farm) = self.farm_mut(fill.buyer) {
+            farm.money -= total;
+            farm.store_veggies(fill.veggie, fill.qty);
+            farm.stats.trades += 1;
+        }
+
+        self.prices.sell(catalog, fill.veggie, fill.qty);
+        self.market.fills.push_back(fill);
+        if self.market.fills.len() > MAX_FILLS {
+            self.market.fills.pop_front();
+        }
+    }
+
+    /// Finds the best pair of crossing orders by different players.
+    fn next_match(&self, veggie: Veggie) -> Option<(OrderId, OrderId)> {
+        let mut sells: Vec<_> = self
+            .market
+            .orders
+            .iter()
+            .filter(|(_, o)| o.veggie == veggie && o.side == Side::Sell)
+            .collect();
+        let mut buys: Vec<_> = self
+            .market
+            .orders
+            .iter()
+            .filter(|(_, o)| o.veggie == veggie && o.side == Side::Buy)
+            .collect();
+
+        sells.sort_by_key(|(id, o)| (o.price, **id));
+        buys.sort_by_key(|(id, o)| (std::cmp::Reverse(o.price), **id));
+
+        sells.iter().find_map(|(sell_id, sell)| {
+            buys.iter()
+                .find(|(_, buy)| buy.price >= sell.price && buy.user_id != sell.user_id)
+                .map(|(buy_id, _)| (**sell_id, **buy_id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{catalog, stock, ALICE, BOB, CARROT};
+
+    const MONEY: Money = 100;
+
+    /// Alice and Bob with empty silos and some money.
+    fn world(catalog: &Catalog) -> State {
+        let mut state = crate::testing::world(catalog);
+        for user_id in [ALICE, BOB] {
+            state.farm_mut(user_id).unwrap().money = MONEY;
+        }
+        state
+    }
+
+    fn order(
+        state: &mut State,
+        catalog: &Catalog,
+        user_id: UserId,
+        side: Side,
+        qty: Quantity,
+        price: Money,
+    ) {
+        state
+            .place_order(catalog, user_id, side, CARROT, qty, price)
+            .unwrap();
+    }
+
+    #[test]
+    fn fills_at_the_price_of_the_older_order() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        stock(&mut state, ALICE, CARROT, 5);
+
+        order(&mut state, &catalog, ALICE, Side::Sell, 5, 8);
+        order(&mut state, &catalog, BOB, Side::Buy, 3, 10);
+
+        assert_eq!(state.market.fills.len(), 1);
+        let fill = &state.market.fills[0];
+        assert_eq!(
+            (fill.seller, fill.buyer, fill.qty, fill.price),
+            (ALICE, BOB, 3, 8)
+        );
+
+        let alice = state.farm(ALICE).unwrap();
+        assert_eq!(alice.money, MONEY + 24);
+        assert_eq!(alice.count_veggies(CARROT), 2);
+        let bob = state.farm(BOB).unwrap();
+        assert_eq!(bob.money, MONEY - 24);
+        assert_eq!(bob.count_veggies(CARROT), 3);
+
+        // The rest of the sell order stays in the book.
+        assert_eq!(state.market.orders.len(), 1);
+        assert_eq!(state.market.orders[&0].qty, 2);
+        assert_eq!(state.prices.veggies[&CARROT].sold, 3);
+    }
+
+    #[test]
+    fn matches_the_cheapest_sell_of_someone_else() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        stock(&mut state, ALICE, CARROT, 1);
+        stock(&mut state, BOB, CARROT, 1);
+
+        order(&mut state, &catalog, ALICE, Side::Sell, 1, 9);
+        order(&mut state, &catalog, BOB, Side::Sell, 1, 7);
+        order(&mut state, &catalog, ALICE, Side::Buy, 2, 10);
+
+        assert_eq!(state.market.fills.len(), 1);
+        let fill = &state.market.fills[0];
+        assert_eq!((fill.seller, fill.qty, fill.price), (BOB, 1, 7));
+
+        // Alice doesn't trade with herself.
+        assert_eq!(state.market.orders.len(), 2);
+        assert_eq!(state.market.orders[&0].qty, 1);
+        assert_eq!(state.market.orders[&2].qty, 1);
+    }
+
+    #[test]
+    fn drops_orders_that_can_no_longer_be_honored() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        stock(&mut state, ALICE, CARROT, 2);
+
+        order(&mut state, &catalog, ALICE, Side::Sell, 2, 5);
+        state.farm_mut(ALICE).unwrap().silos[0].storage.clear();
+        order(&mut state, &catalog, BOB, Side::Buy, 2, 5);
+
+        assert!(state.market.fills.is_empty());
+        assert_eq!(state.market.orders.keys().collect::<Vec<_>>(), [&1]);
+        assert_eq!(state.farm(BOB).unwrap().money, MONEY);
+    }
+}
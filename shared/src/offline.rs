@@ -0,0 +1,241 @@
+use crate::{
+    Audience, Catalog, Climate, Farm, Player, Redaction, RefillSchedule, Seed, State, Time, UserId,
+};
+use rand::{
+    rngs::{SmallRng, StdRng},
+    Rng, SeedableRng,
+};
+
+/// Every farm draws from its own generator for every tick, seeded by that
+/// tick, so that a farm that is caught up later rolls the same numbers as if
+/// it had been ticked all along.
+pub(crate) fn farm_rng(tick_seed: Seed, user_id: UserId, time: Time) -> SmallRng {
+    SmallRng::seed_from_u64(tick_seed ^ (user_id as Seed).rotate_left(32) ^ time)
+}
+
+/// The seed of every tick follows from the secret seed, so the server can
+/// tell it for any past tick. Players only learn it once the tick comes,
+/// and can't work back to the secret seed from it.
+pub(crate) fn tick_seed(secret_seed: Seed, time: Time) -> Seed {
+    StdRng::seed_from_u64(secret_seed ^ time.wrapping_mul(0x9e37_79b9_7f4a_7c15)).gen()
+}
+
+impl Player {
+    /// Farms of idle players aren't ticked, they catch up once someone
+    /// interacts with them. Trucks on the road keep a farm busy, their
+    /// arrival can't wait.
+    pub fn is_idle(&self, time: Time) -> bool {
+        !self.is_online(time) && self.farm.trucks.iter().all(|t| t.trip.is_none())
+    }
+}
+
+impl Farm {
+    /// Nothing changes on a farm without crops, with full silos and with
+    /// all machines at rest, no matter how long it is left alone.
+    fn is_dormant(&self) -> bool {
+        self.fields.iter().all(|f| f.veggies.is_none())
+            && self.silos.iter().all(|s| s.storage.len() >= s.max_storage)
+            && self.tractors.iter().all(|t| t.is_ready())
+            && self.trucks.iter().all(|t| t.is_ready())
+    }
+
+    /// Advances the farm by one tick, exactly as the world tick would.
+    fn advance(
+        &mut self,
+        user_id: UserId,
+        time: Time,
+        seed: Seed,
+        catalog: &Catalog,
+        climate: &Climate,
+        refills: &RefillSchedule,
+    ) {
+        if Climate::changes_at(time) {
+            self.weather(climate.weather(time), time, catalog);
+        }
+        let mut rng = farm_rng(seed, user_id, time);
+        self.tick(time, catalog, climate, refills, &mut rng);
+    }
+}
+
+impl State {
+    /// A new world with a secret seed of its own.
+    pub fn new() -> Self {
+        State {
+            secret_seed: rand::random(),
+            ..State::default()
+        }
+    }
+
+    /// Brings a farm up to the current time.
+    pub(crate) fn catch_up(&mut self, catalog: &Catalog, user_id: UserId) {
+        let climate = self.climate();
+        let Some(player) = self.players.get_mut(&user_id) else {
+            return;
+        };
+
+        while player.synced < self.time {
+            if player.farm.is_dormant() {
+                player.synced = self.time;
+                break;
+            }
+            player.synced += 1;
+            // Replicas without the secret seed are only ever behind by the
+            // current tick.
+            let seed = if player.synced == self.time {
+                self.tick_seed
+            } else {
+                tick_seed(self.secret_seed, player.synced)
+            };
+            player.farm.advance(
+                user_id,
+                player.synced,
+                seed,
+                catalog,
+                &climate,
+                &self.refills,
+            );
+        }
+    }
+
+    /// The seed of the next tick, only the server can tell it.
+    pub fn next_tick_seed(&self) -> Seed {
+        tick_seed(self.secret_seed, self.time + 1)
+    }
+
+    pub fn climate(&self) -> Climate {
+        Climate { seed: self.seed }
+    }
+
+    pub(crate) fn connect(&mut self, catalog: &Catalog, user_id: UserId) {
+        self.catch_up(catalog, user_id);
+        let Some(player) = self.players.get_mut(&user_id) else {
+            return;
+        };
+        player.connections += 1;
+        if player.connections == 1 {
+            self.tell(Audience::Everyone, Redaction::Online(user_id, true));
+        }
+    }
+
+    pub(crate) fn disconnect(&mut self, user_id: UserId) {
+        let Some(player) = self.players.get_mut(&user_id) else {
+            return;
+        };
+        player.connections = player.connections.saturating_sub(1);
+        if player.connections == 0 {
+            self.tell(Audience::Everyone, Redaction::Online(user_id, false));
+        }
+    }
+
+    /// Forgets all connections, for when the server starts over.
+    pub fn reset_connections(&mut self) {
+        for player in self.players.values_mut() {
+            player.connections = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_player, broadcast, spec, tick, update, wait, ALICE, BOB};
+    use crate::{Event, EventData, Field, Veggie, VeggieQty, ONE_MINUTE, SPEED, WEATHER_LENGTH};
+
+    fn catalog() -> Catalog {
+        Catalog {
+            veggies: vec![spec(Veggie(0), 600, 10), spec(Veggie(1), 900, 10)],
+        }
+    }
+
+    /// Alice with planted fields and an empty silo, away since the start.
+    fn world(catalog: &Catalog) -> State {
+        let mut state = State {
+            secret_seed: 7,
+            ..State::default()
+        };
+
+        let farm = add_player(&mut state, catalog, ALICE, "alice");
+        farm.fields = vec![Field::new(), Field::new(), Field::new()];
+        farm.tractors.push(crate::Tractor::new());
+        for veggie in [Veggie(0), Veggie(1)] {
+            farm.silos[0].storage.push_back(VeggieQty::new(veggie, 3));
+            farm.plant_field(0, catalog).unwrap();
+        }
+        state
+    }
+
+    fn farm(state: &State) -> String {
+        format!("{:?}", state.players[&ALICE].farm)
+    }
+
+    #[test]
+    fn catching_up_rolls_like_ticking() {
+        let catalog = catalog();
+        let mut lazy = world(&catalog);
+        let mut eager = lazy.clone();
+        eager.players.get_mut(&ALICE).unwrap().connections = 1;
+
+        for _ in 0..2 * WEATHER_LENGTH {
+            tick(&mut eager, &catalog);
+            tick(&mut lazy, &catalog);
+        }
+        assert!(lazy.players[&ALICE].synced < lazy.time);
+        assert_ne!(farm(&lazy), farm(&eager));
+
+        lazy.catch_up(&catalog, ALICE);
+        assert_eq!(farm(&lazy), farm(&eager));
+    }
+
+    #[test]
+    fn views_roll_like_the_server() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        for _ in 0..4 * ONE_MINUTE * SPEED {
+            tick(&mut state, &catalog);
+        }
+
+        let mut view = state.view(&catalog, ALICE);
+        for _ in 0..WEATHER_LENGTH {
+            let event = tick(&mut state, &catalog);
+            view.update(&catalog, event).unwrap();
+        }
+
+        let me = format!("{:?}", view.me().unwrap().farm);
+        assert_ne!(me, farm(&state));
+
+        state.catch_up(&catalog, ALICE);
+        assert_eq!(me, farm(&state));
+    }
+
+    #[test]
+    fn restored_farms_come_back_like_on_the_server() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        add_player(&mut state, &catalog, BOB, "bob");
+        for _ in 0..ONE_MINUTE * SPEED {
+            tick(&mut state, &catalog);
+        }
+        let archived = state.players[&ALICE].clone();
+        update(&mut state, &catalog, None, Event::RemovePlayer(ALICE));
+
+        // Alice kept her page open while her farm was archived.
+        let mut views = vec![state.view(&catalog, ALICE), state.view(&catalog, BOB)];
+        wait(&mut state, &mut views, &catalog, 4 * ONE_MINUTE * SPEED);
+        for event in [
+            Event::RestorePlayer(ALICE, Box::new(archived)),
+            Event::Connect(ALICE),
+        ] {
+            let event = EventData {
+                event,
+                user_id: None,
+                seed: 0,
+                event_idx: state.next_event_idx,
+            };
+            broadcast(&mut state, &mut views, &catalog, event);
+        }
+        wait(&mut state, &mut views, &catalog, WEATHER_LENGTH);
+
+        assert_eq!(format!("{:?}", views[0].me().unwrap().farm), farm(&state));
+        assert!(views[1].others().any(|(user_id, _)| user_id == ALICE));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{Catalog, Money, Quantity, Time, Veggie, VeggieQty, ONE_MINUTE, SPEED};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Ticks between two price updates.
+pub const PRICE_INTERVAL: Time = ONE_MINUTE * SPEED;
+pub const PRICE_HISTORY: usize = 60;
+/// Keeps prices from swinging wildly on tiny volumes.
+pub const PRICE_DAMPING: Quantity = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VeggiePrice {
+    pub price: Money,
+    /// Harvested world-wide since the last update.
+    pub harvested: Quantity,
+    /// Sold on the market world-wide since the last update.
+    pub sold: Quantity,
+    /// Past prices, oldest first.
+    pub history: VecDeque<Money>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Prices {
+    pub veggies: BTreeMap<Veggie, VeggiePrice>,
+}
+
+impl Prices {
+    /// Veggies without a price yet go for their base value.
+    pub fn price(&self, catalog: &Catalog, veggie: Veggie) -> Money {
+        self.veggies.get(&veggie).map_or_else(
+            || catalog.get(veggie).map_or(0, |spec| spec.base_value),
+            |v| v.price,
+        )
+    }
+
+    pub fn value(&self, catalog: &Catalog, veggies: &VeggieQty) -> Money {
+        self.price(catalog, veggies.veggie())
+            .saturating_mul(veggies.qty())
+    }
+
+    /// Records a harvest and returns what it pays.
+    pub fn harvest(&mut self, catalog: &Catalog, veggies: &VeggieQty) -> Money {
+        let v = self.entry(catalog, veggies.veggie());
+        v.harvested = v.harvested.saturating_add(veggies.qty());
+        self.value(catalog, veggies)
+    }
+
+    pub fn sell(&mut self, catalog: &Catalog, veggie: Veggie, qty: Quantity) {
+        let v = self.entry(catalog, veggie);
+        v.sold = v.sold.saturating_add(qty);
+    }
+
+    fn entry(&mut self, catalog: &Catalog, veggie: Veggie) -> &mut VeggiePrice {
+        self.veggies.entry(veggie).or_insert_with(|| VeggiePrice {
+            price: catalog.get(veggie).map_or(0, |spec| spec.base_value),
+            harvested: 0,
+            sold: 0,
+            history: VecDeque::new(),
+        })
+    }
+
+    /// Moves every price halfway towards what demand relative to supply
+    /// suggests, within a tenth and ten times the base value.
+    pub fn update(&mut self, catalog: &Catalog) {
+        for veggie in catalog.iter() {
+            self.entry(catalog, veggie);
+        }
+        self.veggies.retain(|veggie, _| catalog.contains(*veggie));
+
+        for (veggie, v) in &mut self.veggies {
+            let base = catalog.get(*veggie).map_or(0, |spec| spec.base_value) as u128;
+            let demand = v.sold as u128 + PRICE_DAMPING as u128;
+            let supply = v.harvested as u128 + PRICE_DAMPING as u128;
+            let floor = (base / 10).max(1);
+            let target =
+                (base.saturating_mul(demand) / supply).clamp(floor, (base * 10).max(floor));
+            let price = (v.price as u128 + target) / 2;
+
+            v.price = Money::try_from(price).unwrap_or(Money::MAX);
+            v.harvested = 0;
+            v.sold = 0;
+
+            v.history.push_back(v.price);
+            if v.history.len() > PRICE_HISTORY {
+                v.history.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{spec, CARROT};
+
+    fn catalog(base_value: Money) -> Catalog {
+        Catalog {
+            veggies: vec![spec(CARROT, 1, base_value)],
+        }
+    }
+
+    #[test]
+    fn moves_halfway_within_bounds() {
+        let catalog = catalog(10);
+        let mut prices = Prices::default();
+
+        prices.sell(&catalog, CARROT, 990);
+        prices.update(&catalog);
+        // Halfway up to the ceiling of ten times the base value.
+        assert_eq!(prices.price(&catalog, CARROT), 55);
+
+        prices.harvest(&catalog, &VeggieQty::new(CARROT, 990));
+        prices.update(&catalog);
+        // Halfway down to the floor of a tenth of the base value, at least 1.
+        assert_eq!(prices.price(&catalog, CARROT), 28);
+
+        let v = &prices.veggies[&CARROT];
+        assert_eq!((v.sold, v.harvested), (0, 0));
+        assert_eq!(v.history, [55, 28]);
+    }
+
+    #[test]
+    fn saturates_instead_of_overflowing() {
+        let catalog = catalog(Money::MAX);
+        let mut prices = Prices::default();
+
+        prices.sell(&catalog, CARROT, Quantity::MAX);
+        prices.sell(&catalog, CARROT, Quantity::MAX);
+        assert_eq!(prices.veggies[&CARROT].sold, Quantity::MAX);
+        prices.update(&catalog);
+        assert_eq!(prices.price(&catalog, CARROT), Money::MAX);
+
+        let paid = prices.harvest(&catalog, &VeggieQty::new(CARROT, 2));
+        assert_eq!(paid, Money::MAX);
+    }
+
+    #[test]
+    fn keeps_a_limited_history() {
+        let catalog = catalog(10);
+        let mut prices = Prices::default();
+
+        for _ in 0..=PRICE_HISTORY {
+            prices.update(&catalog);
+        }
+        assert_eq!(prices.veggies[&CARROT].history.len(), PRICE_HISTORY);
+    }
+}
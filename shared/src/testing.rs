@@ -0,0 +1,126 @@
+//! Fixtures for the tests of this crate.
+
+use crate::{
+    Catalog, Event, EventData, Farm, Money, Quantity, Res, State, Time, UserId, Veggie, VeggieQty,
+    VeggieSpec, View,
+};
+
+pub const ALICE: UserId = 1;
+pub const BOB: UserId = 2;
+pub const CARROT: Veggie = Veggie(0);
+
+pub fn spec(id: Veggie, growth_time: Time, base_value: Money) -> VeggieSpec {
+    VeggieSpec {
+        id,
+        name: format!("Veggie {}", id.0),
+        weight: 1,
+        growth_time,
+        base_value,
+        sprite: String::new(),
+        seasons: Vec::new(),
+    }
+}
+
+/// Carrots that grow right away and are worth 10.
+pub fn catalog() -> Catalog {
+    Catalog {
+        veggies: vec![spec(CARROT, 1, 10)],
+    }
+}
+
+/// Alice and Bob with empty silos.
+pub fn world(catalog: &Catalog) -> State {
+    let mut state = State::default();
+    add_player(&mut state, catalog, ALICE, "alice");
+    add_player(&mut state, catalog, BOB, "bob");
+    state
+}
+
+/// Adds a player and empties their silo.
+pub fn add_player<'a>(
+    state: &'a mut State,
+    catalog: &Catalog,
+    user_id: UserId,
+    username: &str,
+) -> &'a mut Farm {
+    update(
+        state,
+        catalog,
+        None,
+        Event::AddPlayer(user_id, username.to_string()),
+    );
+    let farm = state.farm_mut(user_id).unwrap();
+    farm.silos[0].storage.clear();
+    farm
+}
+
+pub fn update(state: &mut State, catalog: &Catalog, user_id: Option<UserId>, event: Event) {
+    let event_idx = state.next_event_idx;
+    state
+        .update(
+            catalog,
+            EventData {
+                event,
+                user_id,
+                seed: 0,
+                event_idx,
+            },
+        )
+        .unwrap();
+}
+
+/// Ticks like the server does and returns the tick.
+pub fn tick(state: &mut State, catalog: &Catalog) -> EventData {
+    let event = EventData {
+        event: Event::Tick,
+        user_id: None,
+        seed: state.next_tick_seed(),
+        event_idx: state.next_event_idx,
+    };
+    state.update(catalog, event.clone()).unwrap();
+    event
+}
+
+pub fn stock(state: &mut State, user_id: UserId, veggie: Veggie, qty: Quantity) {
+    let storage = &mut state.farm_mut(user_id).unwrap().silos[0].storage;
+    storage.push_back(VeggieQty::new(veggie, qty));
+}
+
+/// Sends an event to the views the way the server does.
+pub fn broadcast(state: &mut State, views: &mut [View], catalog: &Catalog, event: EventData) {
+    let mut filter = event.filter();
+    state.update(catalog, event.clone()).unwrap();
+    filter.complete(catalog, &event, state);
+
+    for view in views {
+        match filter.res(&event, view.receiver()) {
+            Res::Event(event) => view.update(catalog, event).unwrap(),
+            Res::Skip(event_idx) => view.skip(event_idx).unwrap(),
+            Res::Sync(sync) => *view = sync.state,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Ticks and sends the trucks that are done on to their next stop.
+pub fn wait(state: &mut State, views: &mut [View], catalog: &Catalog, ticks: Time) {
+    for _ in 0..ticks {
+        let event = EventData {
+            event: Event::Tick,
+            user_id: None,
+            seed: state.next_tick_seed(),
+            event_idx: state.next_event_idx,
+        };
+        broadcast(state, views, catalog, event);
+
+        for (user_id, truck_index) in state.arrivals() {
+            let event = EventData {
+                event: Event::Arrive(user_id, truck_index),
+                user_id: None,
+                seed: 0,
+                event_idx: state.next_event_idx,
+            };
+            broadcast(state, views, catalog, event);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{check, Audience, Redaction, RejectReason, State, Time, UserId, ONE_MINUTE, SPEED};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+pub type OfferId = u64;
+
+pub const OFFER_TIMEOUT: Time = 5 * ONE_MINUTE * SPEED;
+pub const MAX_OFFERS: usize = 10;
+
+/// A proposal to exchange the cargo of two trucks. Nothing happens until the
+/// receiving player accepts it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TradeOffer {
+    pub from: UserId,
+    pub offered_truck: usize,
+    pub to: UserId,
+    pub requested_truck: usize,
+    pub expires: Time,
+}
+
+impl TradeOffer {
+    /// Only the two of them know about an offer.
+    fn parties(&self) -> Audience {
+        Audience::Only(BTreeSet::from([self.from, self.to]))
+    }
+}
+
+impl State {
+    pub fn propose_trade(
+        &mut self,
+        from: UserId,
+        offered_truck: usize,
+        to: UserId,
+        requested_truck: usize,
+    ) -> Result<(), RejectReason> {
+        check(from != to, RejectReason::SelfTrade)?;
+        let pending = self.offers.values().filter(|o| o.from == from).count();
+        check(pending < MAX_OFFERS, RejectReason::TooManyOffers)?;
+        self.farm(from)
+            .ok_or(RejectReason::UnknownPlayer)?
+            .loaded_truck(offered_truck)?;
+        let other = self.farm(to).ok_or(RejectReason::UnknownPlayer)?;
+        check(
+            requested_truck < other.trucks.len(),
+            RejectReason::NoSuchBuilding,
+        )?;
+
+        let offer = TradeOffer {
+            from,
+            offered_truck,
+            to,
+            requested_truck,
+            expires: self.time + OFFER_TIMEOUT,
+        };
+        self.offers.insert(self.next_offer_id, offer.clone());
+        self.tell(
+            offer.parties(),
+            Redaction::Offer(self.next_offer_id, Some(offer)),
+        );
+        self.next_offer_id += 1;
+
+        Ok(())
+    }
+
+    /// Sends the offering truck on its way, the exchange itself happens when
+    /// it arrives.
+    pub fn accept_trade(&mut self, user_id: UserId, offer_id: OfferId) -> Result<(), RejectReason> {
+        let offer = self
+            .offers
+            .get(&offer_id)
+            .filter(|offer| offer.to == user_id)
+            .ok_or(RejectReason::NoSuchOffer)?
+            .clone();
+        self.dispatch(
+            offer.from,
+            offer.offered_truck,
+            offer.to,
+            offer.requested_truck,
+        )?;
+        self.offers.remove(&offer_id);
+        self.tell(offer.parties(), Redaction::Offer(offer_id, None));
+
+        Ok(())
+    }
+
+    /// Declines an offer received, or withdraws an offer made.
+    pub fn decline_trade(
+        &mut self,
+        user_id: UserId,
+        offer_id: OfferId,
+    ) -> Result<(), RejectReason> {
+        let offer = self
+            .offers
+            .get(&offer_id)
+            .filter(|offer| offer.to == user_id || offer.from == user_id)
+            .ok_or(RejectReason::NoSuchOffer)?;
+        self.tell(offer.parties(), Redaction::Offer(offer_id, None));
+        self.offers.remove(&offer_id);
+
+        Ok(())
+    }
+
+    pub(crate) fn expire_offers(&mut self) {
+        let time = self.time;
+        self.offers.retain(|_, offer| offer.expires > time);
+    }
+}
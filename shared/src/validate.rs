@@ -0,0 +1,709 @@
+use crate::{Catalog, Channel, Event, Farm, State, Truck, UserId};
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, iter};
+use strum::Display;
+
+/// Why a player's event was turned down.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "title_case")]
+pub enum RejectReason {
+    NotAllowed,
+    UnknownPlayer,
+    NotEnoughMoney,
+    NoSuchBuilding,
+    NotUpgradable,
+    SiloEmpty,
+    NoFreeField,
+    TractorsBusy,
+    NotRipe,
+    TrucksBusy,
+    TrucksFull,
+    TruckEmpty,
+    SelfTrade,
+    TooManyOffers,
+    NoSuchOffer,
+    NoSuchContract,
+    ContractTaken,
+    TooLate,
+    WrongCargo,
+    InvalidName,
+    NameTaken,
+    AlreadyInCoop,
+    NoSuchCoop,
+    CoopFull,
+    NotInCoop,
+    NotLeader,
+    NoSuchMember,
+    CoopSiloFull,
+    InvalidMessage,
+    InvalidOrder,
+    TooManyOrders,
+    NotEnoughVeggies,
+    NoSuchOrder,
+}
+
+impl RejectReason {
+    pub fn description(self) -> &'static str {
+        match self {
+            RejectReason::NotAllowed => "Players can't do that",
+            RejectReason::UnknownPlayer => "There is no such player",
+            RejectReason::NotEnoughMoney => "Not enough money",
+            RejectReason::NoSuchBuilding => "There is no such building",
+            RejectReason::NotUpgradable => "This building can't be upgraded",
+            RejectReason::SiloEmpty => "The silo is empty",
+            RejectReason::NoFreeField => "There is no free field",
+            RejectReason::TractorsBusy => "All tractors are busy",
+            RejectReason::NotRipe => "The field isn't ready for harvest",
+            RejectReason::TrucksBusy => "The trucks are on the road",
+            RejectReason::TrucksFull => "The trucks are full",
+            RejectReason::TruckEmpty => "The truck is empty",
+            RejectReason::SelfTrade => "You can't trade with yourself",
+            RejectReason::TooManyOffers => "Too many open offers",
+            RejectReason::NoSuchOffer => "The offer is gone",
+            RejectReason::NoSuchContract => "The contract is gone",
+            RejectReason::ContractTaken => "Someone else took the contract",
+            RejectReason::TooLate => "The truck wouldn't make it in time",
+            RejectReason::WrongCargo => "The truck doesn't carry what the contract asks for",
+            RejectReason::InvalidName => "Invalid name",
+            RejectReason::NameTaken => "The name is taken",
+            RejectReason::AlreadyInCoop => "You are in a co-op already",
+            RejectReason::NoSuchCoop => "There is no such co-op",
+            RejectReason::CoopFull => "The co-op is full",
+            RejectReason::NotInCoop => "You aren't in a co-op",
+            RejectReason::NotLeader => "Only the leader can do that",
+            RejectReason::NoSuchMember => "There is no such member",
+            RejectReason::CoopSiloFull => "The co-op silo is full",
+            RejectReason::InvalidMessage => "Invalid message",
+            RejectReason::InvalidOrder => "Invalid order",
+            RejectReason::TooManyOrders => "Too many open orders",
+            RejectReason::NotEnoughVeggies => "Not enough veggies",
+            RejectReason::NoSuchOrder => "The order is gone",
+        }
+    }
+}
+
+pub(crate) fn check(condition: bool, reason: RejectReason) -> Result<(), RejectReason> {
+    if condition {
+        Ok(())
+    } else {
+        Err(reason)
+    }
+}
+
+impl Farm {
+    /// A truck that can be sent off with its cargo.
+    pub(crate) fn loaded_truck(&self, truck_index: usize) -> Result<&Truck, RejectReason> {
+        let truck = self
+            .trucks
+            .get(truck_index)
+            .ok_or(RejectReason::NoSuchBuilding)?;
+        check(truck.is_ready(), RejectReason::TrucksBusy)?;
+        check(truck.veggies().is_some(), RejectReason::TruckEmpty)?;
+        Ok(truck)
+    }
+}
+
+impl State {
+    /// Tries an event of a player on a copy of the state it is about to be
+    /// applied to, so the player learns why it would have no effect. The
+    /// copy holds only the farms the event may touch.
+    pub fn validate(
+        &self,
+        catalog: &Catalog,
+        event: &Event,
+        user_id: UserId,
+    ) -> Result<(), RejectReason> {
+        check(
+            self.players.contains_key(&user_id),
+            RejectReason::UnknownPlayer,
+        )?;
+
+        let players = iter::once(user_id)
+            .chain(self.counterparties(event))
+            .filter_map(|user_id| Some((user_id, self.players.get(&user_id)?.clone())))
+            .collect();
+        let mut state = State {
+            players,
+            time: self.time,
+            next_event_idx: self.next_event_idx,
+            refills: self.refills.clone(),
+            offers: self.offers.clone(),
+            next_offer_id: self.next_offer_id,
+            market: self.market.clone(),
+            prices: self.prices.clone(),
+            seed: self.seed,
+            contracts: self.contracts.clone(),
+            next_contract_id: self.next_contract_id,
+            coops: self.coops.clone(),
+            next_coop_id: self.next_coop_id,
+            // Messages are only added to.
+            chat: VecDeque::new(),
+            tick_seed: self.tick_seed,
+            secret_seed: self.secret_seed,
+            unlocked: Vec::new(),
+            outcomes: Vec::new(),
+        };
+        state.catch_up(catalog, user_id);
+        // Only buying a silo rolls, and what it rolls doesn't matter here.
+        let mut rng = SmallRng::seed_from_u64(0);
+        state.apply(catalog, event.clone(), user_id, &mut rng)
+    }
+
+    /// Other players whose farms an event may look at or change.
+    fn counterparties(&self, event: &Event) -> Vec<UserId> {
+        match event {
+            Event::ProposeTrade(_, to, _) | Event::SendMessage(Channel::Direct(to), _) => {
+                vec![*to]
+            }
+            Event::AcceptTrade(offer_id) => self
+                .offers
+                .get(offer_id)
+                .map(|offer| offer.from)
+                .into_iter()
+                .collect(),
+            Event::KickMember(member) => vec![*member],
+            // Orders are matched right away.
+            Event::PlaceOrder(_, veggie, _, _) => self
+                .market
+                .orders
+                .values()
+                .filter(|order| order.veggie == *veggie)
+                .map(|order| order.user_id)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{catalog, stock, update, ALICE, BOB, CARROT};
+    use crate::{
+        Building, Channel, Contract, Countermeasure, Field, Side, Veggie, VeggieQty, GROWTH_RATE,
+    };
+
+    /// Alice and Bob with empty silos, a field each and a truck each.
+    fn world(catalog: &Catalog) -> State {
+        let mut state = crate::testing::world(catalog);
+        for user_id in [ALICE, BOB] {
+            let farm = state.farm_mut(user_id).unwrap();
+            farm.fields.push(Field::new());
+            farm.trucks.push(Truck::new());
+        }
+        state
+    }
+
+    /// Validates an event and applies it if it passes.
+    fn play(
+        state: &mut State,
+        catalog: &Catalog,
+        user_id: UserId,
+        event: Event,
+    ) -> Result<(), RejectReason> {
+        state.validate(catalog, &event, user_id)?;
+        update(state, catalog, Some(user_id), event);
+        Ok(())
+    }
+
+    fn load(state: &mut State, user_id: UserId) {
+        state.farm_mut(user_id).unwrap().trucks[0].veggies = Some(VeggieQty::new(CARROT, 1));
+    }
+
+    #[test]
+    fn rejects_server_events_and_strangers() {
+        let catalog = catalog();
+        let state = world(&catalog);
+
+        for event in [
+            Event::Tick,
+            Event::AddPlayer(3, "carol".to_string()),
+            Event::EditPlayer(ALICE, "mallory".to_string()),
+            Event::RemovePlayer(BOB),
+            Event::Connect(ALICE),
+            Event::Disconnect(ALICE),
+            Event::Arrive(BOB, 0),
+        ] {
+            assert_eq!(
+                state.validate(&catalog, &event, ALICE),
+                Err(RejectReason::NotAllowed)
+            );
+        }
+        assert_eq!(
+            state.validate(&catalog, &Event::LeaveCoop, 3),
+            Err(RejectReason::UnknownPlayer)
+        );
+    }
+
+    #[test]
+    fn buildings() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::Upgrade(Building::Tractor, 0)
+            ),
+            Err(RejectReason::NotUpgradable)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::Upgrade(Building::Silo, 1)
+            ),
+            Err(RejectReason::NoSuchBuilding)
+        );
+
+        state.farm_mut(ALICE).unwrap().money = 0;
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::Buy(Building::Field)),
+            Err(RejectReason::NotEnoughMoney)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::Upgrade(Building::Silo, 0)
+            ),
+            Err(RejectReason::NotEnoughMoney)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::BuyCountermeasure(Countermeasure::Pesticide)
+            ),
+            Err(RejectReason::NotEnoughMoney)
+        );
+
+        state.farm_mut(ALICE).unwrap().money = 1_000_000;
+        let fields = state.farm(ALICE).unwrap().fields.len();
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::Buy(Building::Field)),
+            Ok(())
+        );
+        assert_eq!(state.farm(ALICE).unwrap().fields.len(), fields + 1);
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::Upgrade(Building::Silo, 0)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::BuyCountermeasure(Countermeasure::Pesticide)
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn planting_and_harvesting() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantField(1)),
+            Err(RejectReason::NoSuchBuilding)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantField(0)),
+            Err(RejectReason::SiloEmpty)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::HarvestField(0)),
+            Err(RejectReason::NotRipe)
+        );
+
+        stock(&mut state, ALICE, CARROT, 5);
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantField(0)),
+            Ok(())
+        );
+        // The rest of the veggies stay in the silo.
+        let farm = state.farm(ALICE).unwrap();
+        assert_eq!(farm.fields[0].veggies.as_ref().map(VeggieQty::qty), Some(3));
+        assert_eq!(farm.silos[0].storage[0].qty(), 2);
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantField(0)),
+            Err(RejectReason::TractorsBusy)
+        );
+        state.farm_mut(ALICE).unwrap().tractors[0].wait = 0;
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantField(0)),
+            Err(RejectReason::NoFreeField)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::HarvestField(0)),
+            Err(RejectReason::NotRipe)
+        );
+
+        let field = &mut state.farm_mut(ALICE).unwrap().fields[0];
+        field.age = field.growth_time * GROWTH_RATE;
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::HarvestField(0)),
+            Ok(())
+        );
+        assert!(state.farm(ALICE).unwrap().fields[0].veggies.is_none());
+    }
+
+    #[test]
+    fn loading_trucks() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::LoadTruck(0)),
+            Err(RejectReason::SiloEmpty)
+        );
+
+        stock(&mut state, ALICE, CARROT, 1);
+        stock(&mut state, ALICE, CARROT, 1);
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::LoadTruck(0)),
+            Ok(())
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::LoadTruck(0)),
+            Err(RejectReason::TrucksFull)
+        );
+        assert_eq!(state.farm(ALICE).unwrap().silos[0].storage.len(), 1);
+
+        state.farm_mut(ALICE).unwrap().trucks[0].wait = 1;
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::LoadTruck(0)),
+            Err(RejectReason::TrucksBusy)
+        );
+    }
+
+    #[test]
+    fn trading() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::ProposeTrade(0, ALICE, 0)
+            ),
+            Err(RejectReason::SelfTrade)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::ProposeTrade(0, BOB, 0)),
+            Err(RejectReason::TruckEmpty)
+        );
+        load(&mut state, ALICE);
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::ProposeTrade(0, BOB, 1)),
+            Err(RejectReason::NoSuchBuilding)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::ProposeTrade(0, 3, 0)),
+            Err(RejectReason::UnknownPlayer)
+        );
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::ProposeTrade(0, BOB, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::ProposeTrade(0, BOB, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::AcceptTrade(0)),
+            Err(RejectReason::NoSuchOffer)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::AcceptTrade(0)),
+            Ok(())
+        );
+        assert!(!state.farm(ALICE).unwrap().trucks[0].is_ready());
+
+        // The second offer is stale now that the truck is on its way.
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::AcceptTrade(1)),
+            Err(RejectReason::TrucksBusy)
+        );
+        assert!(state.offers.contains_key(&1));
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::DeclineTrade(1)),
+            Ok(())
+        );
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::DeclineTrade(1)),
+            Err(RejectReason::NoSuchOffer)
+        );
+    }
+
+    #[test]
+    fn contracts() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+        state.contracts.insert(
+            0,
+            Contract {
+                veggie: CARROT,
+                qty: 2,
+                reward: 10,
+                deadline: crate::TRAVEL_TIME,
+                taken_by: None,
+            },
+        );
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::DeliverContract(0, 1)),
+            Err(RejectReason::NoSuchContract)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::DeliverContract(0, 0)),
+            Err(RejectReason::TruckEmpty)
+        );
+        load(&mut state, ALICE);
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::DeliverContract(0, 0)),
+            Err(RejectReason::WrongCargo)
+        );
+
+        state.farm_mut(ALICE).unwrap().trucks[0].veggies = Some(VeggieQty::new(CARROT, 2));
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::DeliverContract(0, 0)),
+            Ok(())
+        );
+        assert_eq!(state.contracts[&0].taken_by, Some(ALICE));
+
+        load(&mut state, BOB);
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::DeliverContract(0, 0)),
+            Err(RejectReason::ContractTaken)
+        );
+        state.contracts.get_mut(&0).unwrap().taken_by = None;
+        update(&mut state, &catalog, None, Event::Tick);
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::DeliverContract(0, 0)),
+            Err(RejectReason::TooLate)
+        );
+    }
+
+    #[test]
+    fn coops() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::CreateCoop(" ".to_string())
+            ),
+            Err(RejectReason::InvalidName)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::LeaveCoop),
+            Err(RejectReason::NotInCoop)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::Deposit(0)),
+            Err(RejectReason::NotInCoop)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantFromCoop),
+            Err(RejectReason::NotInCoop)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::JoinCoop(0)),
+            Err(RejectReason::NoSuchCoop)
+        );
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::CreateCoop("Farmers".to_string())
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::CreateCoop("Growers".to_string())
+            ),
+            Err(RejectReason::AlreadyInCoop)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                BOB,
+                Event::CreateCoop("Farmers".to_string())
+            ),
+            Err(RejectReason::NameTaken)
+        );
+        assert_eq!(play(&mut state, &catalog, BOB, Event::JoinCoop(0)), Ok(()));
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::KickMember(ALICE)),
+            Err(RejectReason::NotLeader)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::KickMember(3)),
+            Err(RejectReason::NoSuchMember)
+        );
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::Deposit(0)),
+            Err(RejectReason::SiloEmpty)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::PlantFromCoop),
+            Err(RejectReason::SiloEmpty)
+        );
+        stock(&mut state, ALICE, CARROT, 1);
+        assert_eq!(play(&mut state, &catalog, ALICE, Event::Deposit(0)), Ok(()));
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::PlantFromCoop),
+            Ok(())
+        );
+
+        state.coops.get_mut(&0).unwrap().silo.max_storage = 0;
+        stock(&mut state, ALICE, CARROT, 1);
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::Deposit(0)),
+            Err(RejectReason::CoopSiloFull)
+        );
+
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::KickMember(BOB)),
+            Ok(())
+        );
+        assert_eq!(play(&mut state, &catalog, ALICE, Event::LeaveCoop), Ok(()));
+        assert!(state.coops.is_empty());
+    }
+
+    #[test]
+    fn chat() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::SendMessage(Channel::Global, " ".to_string())
+            ),
+            Err(RejectReason::InvalidMessage)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::SendMessage(Channel::Direct(ALICE), "hi".to_string())
+            ),
+            Err(RejectReason::UnknownPlayer)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::SendMessage(Channel::Direct(BOB), "hi".to_string())
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::SendMessage(Channel::Global, "hi".to_string())
+            ),
+            Ok(())
+        );
+        assert_eq!(state.chat.len(), 2);
+    }
+
+    #[test]
+    fn orders() {
+        let catalog = catalog();
+        let mut state = world(&catalog);
+
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::PlaceOrder(Side::Buy, Veggie(7), 1, 1)
+            ),
+            Err(RejectReason::InvalidOrder)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::PlaceOrder(Side::Buy, CARROT, 0, 1)
+            ),
+            Err(RejectReason::InvalidOrder)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::PlaceOrder(Side::Sell, CARROT, 1, 1)
+            ),
+            Err(RejectReason::NotEnoughVeggies)
+        );
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::PlaceOrder(Side::Buy, CARROT, 2, u64::MAX)
+            ),
+            Err(RejectReason::NotEnoughMoney)
+        );
+
+        stock(&mut state, ALICE, CARROT, 1);
+        assert_eq!(
+            play(
+                &mut state,
+                &catalog,
+                ALICE,
+                Event::PlaceOrder(Side::Sell, CARROT, 1, 1)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            play(&mut state, &catalog, BOB, Event::CancelOrder(0)),
+            Err(RejectReason::NoSuchOrder)
+        );
+        assert_eq!(
+            play(&mut state, &catalog, ALICE, Event::CancelOrder(0)),
+            Ok(())
+        );
+        assert!(state.market.orders.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Seed, Time, VeggieSpec, ONE_HOUR, ONE_MINUTE, SPEED};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, IntoStaticStr};
+
+pub const SEASON_LENGTH: Time = ONE_HOUR * SPEED;
+pub const WEATHER_LENGTH: Time = 10 * ONE_MINUTE * SPEED;
+pub const FORECAST_LENGTH: usize = 3;
+/// Growth points a crop gains per tick in fair weather and in season.
+pub const GROWTH_RATE: Time = 4;
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Display, EnumIter, Eq, PartialEq, IntoStaticStr,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn at(time: Time) -> Self {
+        match (time / SEASON_LENGTH) % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Display,
+    EnumIter,
+    Eq,
+    PartialEq,
+    IntoStaticStr,
+)]
+#[strum(serialize_all = "title_case")]
+pub enum Weather {
+    #[default]
+    Sunny,
+    Rain,
+    Drought,
+    Frost,
+}
+
+impl Weather {
+    fn roll(season: Season, rng: &mut SmallRng) -> Self {
+        let weights: [(Weather, u32); 4] = match season {
+            Season::Spring => [
+                (Weather::Sunny, 4),
+                (Weather::Rain, 4),
+                (Weather::Drought, 1),
+                (Weather::Frost, 1),
+            ],
+            Season::Summer => [
+                (Weather::Sunny, 5),
+                (Weather::Rain, 2),
+                (Weather::Drought, 3),
+                (Weather::Frost, 0),
+            ],
+            Season::Autumn => [
+                (Weather::Sunny, 3),
+                (Weather::Rain, 5),
+                (Weather::Drought, 1),
+                (Weather::Frost, 1),
+            ],
+            Season::Winter => [
+                (Weather::Sunny, 3),
+                (Weather::Rain, 2),
+                (Weather::Drought, 0),
+                (Weather::Frost, 5),
+            ],
+        };
+
+        weights
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map(|(weather, _)| *weather)
+            .unwrap_or_default()
+    }
+
+    pub fn growth_rate(self) -> Time {
+        match self {
+            Weather::Sunny => GROWTH_RATE,
+            Weather::Rain => GROWTH_RATE * 3 / 2,
+            Weather::Drought => GROWTH_RATE / 2,
+            Weather::Frost => 0,
+        }
+    }
+}
+
+/// The weather of every period follows from the world seed, so that it can
+/// be looked up for any point in time, past or future.
+#[derive(Clone, Copy, Debug)]
+pub struct Climate {
+    pub seed: Seed,
+}
+
+impl Climate {
+    pub fn weather(&self, time: Time) -> Weather {
+        let period = time / WEATHER_LENGTH;
+        let mut rng =
+            SmallRng::seed_from_u64(self.seed ^ period.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        Weather::roll(Season::at(period * WEATHER_LENGTH), &mut rng)
+    }
+
+    /// The weather of the periods after the current one.
+    pub fn forecast(&self, time: Time) -> Vec<Weather> {
+        (1..=FORECAST_LENGTH as Time)
+            .map(|i| self.weather(time + i * WEATHER_LENGTH))
+            .collect()
+    }
+
+    pub fn changes_at(time: Time) -> bool {
+        time.is_multiple_of(WEATHER_LENGTH)
+    }
+
+    /// Veggies grow at half the rate out of their preferred seasons.
+    pub fn growth_rate(&self, spec: &VeggieSpec, time: Time) -> Time {
+        let rate = self.weather(time).growth_rate();
+        if spec.prefers(Season::at(time)) {
+            rate
+        } else {
+            rate / 2
+        }
+    }
+}